@@ -0,0 +1,30 @@
+use std::{env, path::Path, process::Command};
+
+fn main() {
+    let hash = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=LRCFETCH_GIT_HASH={hash}");
+    println!(
+        "cargo:rustc-env=LRCFETCH_TARGET={}",
+        env::var("TARGET").unwrap_or_default()
+    );
+    let mut features = env::vars()
+        .filter_map(|(key, _)| {
+            key.strip_prefix("CARGO_FEATURE_")
+                .map(|feature| feature.to_lowercase().replace('_', "-"))
+        })
+        .filter(|feature| feature != "default")
+        .collect::<Vec<_>>();
+    features.sort();
+    println!("cargo:rustc-env=LRCFETCH_FEATURES={}", features.join(","));
+    for git_file in [".git/HEAD", ".git/refs"] {
+        if Path::new(git_file).exists() {
+            println!("cargo:rerun-if-changed={git_file}");
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::musicdata::{Lyrics, MusicData, fold};
+use crate::settings::Settings;
+use crate::store;
+
+pub const RESPONSES_FILE: &str = "responses.ron";
+pub const MISSES_FILE: &str = "misses.ron";
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Entry {
+    fetched_at: u64,
+    lyrics: Lyrics,
+}
+
+/// lrclib results per track, including misses, so repeated runs skip tracks already asked about.
+pub struct ResponseCache {
+    ttl_secs: u64,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl ResponseCache {
+    /// The cache configured by `response_cache_hours`, or none when that is 0.
+    pub async fn for_settings(settings: &Settings) -> Option<Arc<Self>> {
+        match settings.response_cache_hours {
+            0 => None,
+            hours => Some(Arc::new(Self::load(hours).await)),
+        }
+    }
+
+    pub async fn load(ttl_hours: u64) -> Self {
+        let cache = Self {
+            ttl_secs: ttl_hours * 60 * 60,
+            entries: Mutex::new(store::load(RESPONSES_FILE).await),
+        };
+        let now = unix_now();
+        cache
+            .entries
+            .lock()
+            .unwrap()
+            .retain(|_, entry| cache.fresh(entry, now));
+        cache
+    }
+
+    fn fresh(&self, entry: &Entry, now: u64) -> bool {
+        now.saturating_sub(entry.fetched_at) < self.ttl_secs
+    }
+
+    fn key(data: &MusicData) -> String {
+        format!(
+            "{}\u{1f}{}\u{1f}{}\u{1f}{}",
+            fold(&data.title),
+            data.keys.artist,
+            data.keys.album,
+            data.duration
+        )
+    }
+
+    pub fn get(&self, data: &MusicData) -> Option<Lyrics> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(&Self::key(data))?;
+        self.fresh(entry, unix_now()).then(|| entry.lyrics.clone())
+    }
+
+    pub fn put(&self, data: &MusicData, lyrics: &Lyrics) {
+        self.entries.lock().unwrap().insert(
+            Self::key(data),
+            Entry {
+                fetched_at: unix_now(),
+                lyrics: lyrics.clone(),
+            },
+        );
+    }
+
+    pub async fn save(&self) -> Result<(), tokio::io::Error> {
+        let entries = self.entries.lock().unwrap().clone();
+        store::save(RESPONSES_FILE, &entries).await
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// When each track last came back empty from every provider, for the miss cooldown.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct Misses(HashMap<PathBuf, u64>);
+
+impl Misses {
+    pub fn record(&mut self, path: &Path, lyrics: &Lyrics) {
+        match lyrics {
+            Lyrics::None => self.0.insert(path.to_path_buf(), unix_now()),
+            _ => self.0.remove(path),
+        };
+    }
+
+    /// Whether `path` missed within the last `hours`, so querying it again is pointless.
+    pub fn cooling_down(&self, path: &Path, hours: u64) -> bool {
+        self.0
+            .get(path)
+            .is_some_and(|&missed_at| unix_now().saturating_sub(missed_at) < hours * 60 * 60)
+    }
+}
@@ -0,0 +1,11 @@
+const CHANGELOG: &str = include_str!("../CHANGELOG.md");
+
+/// The changelog section for `version`, without its heading.
+pub fn release_notes(version: &str) -> Option<&'static str> {
+    let heading = format!("## {version}\n");
+    let start = CHANGELOG.find(&heading)? + heading.len();
+    let end = CHANGELOG[start..]
+        .find("\n## ")
+        .map_or(CHANGELOG.len(), |end| start + end);
+    Some(CHANGELOG[start..end].trim())
+}
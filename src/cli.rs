@@ -0,0 +1,1150 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Component, Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use tokio::{sync::Semaphore, task::JoinSet};
+
+use crate::Stats;
+use crate::cache::{MISSES_FILE, Misses, ResponseCache};
+use crate::filter::Filter;
+use crate::library::{
+    Coverage, DURATIONS_FILE, FLAGS_FILE, LibraryEntry, ScanProgress, TrackFlag,
+    apply_duration_overrides, check_lyrics_all, scan_folder, scan_music, to_m3u,
+};
+use crate::lrc;
+use crate::musicdata::{Interner, Lyrics, LyricsStatus, MusicData};
+use crate::preset::{PRESET_FILE, Preset};
+use crate::providers::{Fetched, Registry};
+use crate::review::{Decision, REVIEW_FILE, Review, ReviewEntry};
+use crate::settings::{
+    Settings, TimestampPrecision, WriteOptions, build_client, get_or_create_config,
+};
+use crate::store;
+use crate::throttle::Throttle;
+
+const REFRESH_FILE: &str = "refresh.ron";
+/// How many tracks `scan` reads between progress lines.
+const SCAN_PROGRESS_EVERY: usize = 1000;
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Fetch lyrics files from LRCLIB",
+    args_conflicts_with_subcommands = true,
+    disable_version_flag = true
+)]
+pub struct Cli {
+    /// Music directory to open in the TUI instead of the configured one
+    pub dir: Option<PathBuf>,
+    /// Print a man page to stdout and exit
+    #[arg(long)]
+    pub man: bool,
+    /// Print version
+    #[arg(short = 'V', long)]
+    pub version: bool,
+    /// With --version, also print the git hash, features and build target
+    #[arg(long, requires = "version")]
+    pub verbose: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Open the interactive interface (default)
+    Tui { dir: Option<PathBuf> },
+    /// Fetch missing or plain-only lyrics without the interface
+    Fetch {
+        /// Music directory, or a single audio file
+        dir: Option<PathBuf>,
+        /// Query every track, even ones with synced lyrics or a recent miss, without reusing
+        /// cached responses
+        #[arg(long)]
+        force: bool,
+        /// Query tracks that missed recently too, without reusing cached responses; unlike
+        /// --force, tracks with synced lyrics are still skipped
+        #[arg(long)]
+        ignore_cooldown: bool,
+        /// Fetch for the whole library, even when given a single file
+        #[arg(long)]
+        all: bool,
+    },
+    /// Index the library's tags, re-reading only files changed since the last scan
+    Scan { dir: Option<PathBuf> },
+    /// Summarize lyrics coverage and what is waiting for review or a retry
+    Status { dir: Option<PathBuf> },
+    /// Check existing sidecar files for problems
+    Verify {
+        dir: Option<PathBuf>,
+        /// Rewrite synced sidecars whose timestamps differ from timestamp_precision
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Export the library and its lyrics as JSON
+    Export {
+        dir: Option<PathBuf>,
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Write sidecar files from a JSON export
+    Import {
+        file: PathBuf,
+        /// Replace sidecars that already exist
+        #[arg(long)]
+        overwrite: bool,
+    },
+    /// Copy lyrics embedded in tags out to sidecar files
+    Extract {
+        dir: Option<PathBuf>,
+        /// Replace sidecars that already exist
+        #[arg(long)]
+        overwrite: bool,
+    },
+    /// Show tags and lyrics for a single file
+    Show { file: PathBuf },
+    /// Time the library scan phases
+    Bench { dir: Option<PathBuf> },
+    /// Write a lyrics coverage summary as JSON or an SVG badge
+    Badge {
+        dir: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t = BadgeFormat::Json)]
+        format: BadgeFormat,
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Write an M3U playlist of tracks by lyrics status
+    Playlist {
+        dir: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t = PlaylistLyrics::Missing)]
+        lyrics: PlaylistLyrics,
+        /// Only tracks whose title contains this
+        #[arg(long)]
+        title: Option<String>,
+        /// Only tracks whose artist or album artist contains this
+        #[arg(long)]
+        artist: Option<String>,
+        /// Only tracks whose album contains this
+        #[arg(long)]
+        album: Option<String>,
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Periodically re-check plain-only tracks for synced lyrics
+    Daemon { dir: Option<PathBuf> },
+    /// Print shell completions to stdout
+    Completions { shell: Shell },
+    /// Write the active keymap and theme as a shareable preset
+    ExportPreset {
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Use a keymap and theme preset from another user
+    ImportPreset { file: PathBuf },
+    /// Export tracks waiting for review, with their scored matches, as JSON
+    ExportReview {
+        dir: Option<PathBuf>,
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Apply the decisions from an edited review export
+    ImportReview { file: PathBuf },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum PlaylistLyrics {
+    /// Tracks without any lyrics
+    Missing,
+    /// Tracks with only plain lyrics
+    Plain,
+    /// Tracks without synced lyrics
+    Unsynced,
+    All,
+}
+
+impl PlaylistLyrics {
+    fn matches(&self, status: Option<LyricsStatus>) -> bool {
+        match self {
+            PlaylistLyrics::Missing => matches!(status, None | Some(LyricsStatus::None)),
+            PlaylistLyrics::Plain => status == Some(LyricsStatus::Plain),
+            PlaylistLyrics::Unsynced => matches!(
+                status,
+                None | Some(LyricsStatus::None | LyricsStatus::Plain)
+            ),
+            PlaylistLyrics::All => true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum BadgeFormat {
+    Json,
+    Svg,
+}
+
+pub async fn run(command: Command) -> ExitCode {
+    match command {
+        Command::Tui { dir } => crate::run_tui(dir).await,
+        Command::Fetch {
+            dir: Some(file),
+            force,
+            ignore_cooldown,
+            all: false,
+        } if file.is_file() => match settings(None).await {
+            Some(settings) => fetch_file(settings, file, force, ignore_cooldown).await,
+            None => ExitCode::FAILURE,
+        },
+        // A file given with --all stands for the configured library it belongs to.
+        Command::Fetch {
+            dir,
+            force,
+            ignore_cooldown,
+            ..
+        } => match settings(dir.filter(|dir| !dir.is_file())).await {
+            Some(settings) => fetch(settings, force, ignore_cooldown).await,
+            None => ExitCode::FAILURE,
+        },
+        Command::Scan { dir } => match settings(dir).await {
+            Some(settings) => scan(settings).await,
+            None => ExitCode::FAILURE,
+        },
+        Command::Status { dir } => match settings(dir).await {
+            Some(settings) => status(settings).await,
+            None => ExitCode::FAILURE,
+        },
+        Command::Verify { dir, fix } => match settings(dir).await {
+            Some(settings) => verify(settings, fix).await,
+            None => ExitCode::FAILURE,
+        },
+        Command::Export { dir, output } => match settings(dir).await {
+            Some(settings) => export(settings, output).await,
+            None => ExitCode::FAILURE,
+        },
+        Command::Import { file, overwrite } => match settings(None).await {
+            Some(settings) => import(settings, &file, overwrite).await,
+            None => ExitCode::FAILURE,
+        },
+        Command::Extract { dir, overwrite } => match settings(dir).await {
+            Some(settings) => extract(settings, overwrite).await,
+            None => ExitCode::FAILURE,
+        },
+        Command::Show { file } => show(file).await,
+        Command::Bench { dir } => match settings(dir).await {
+            Some(settings) => bench(settings).await,
+            None => ExitCode::FAILURE,
+        },
+        Command::Badge {
+            dir,
+            format,
+            output,
+        } => match settings(dir).await {
+            Some(settings) => badge(settings, format, output).await,
+            None => ExitCode::FAILURE,
+        },
+        Command::Playlist {
+            dir,
+            lyrics,
+            title,
+            artist,
+            album,
+            output,
+        } => match settings(dir).await {
+            Some(settings) => {
+                let filter = Filter {
+                    title,
+                    artist,
+                    album,
+                    min_duration: settings.min_duration,
+                    include_short: false,
+                    flag: None,
+                    added_within_days: None,
+                    folder: None,
+                };
+                playlist(settings, lyrics, filter, output).await
+            }
+            None => ExitCode::FAILURE,
+        },
+        Command::Daemon { dir } => match settings(dir).await {
+            Some(settings) => daemon(settings).await,
+            None => ExitCode::FAILURE,
+        },
+        Command::Completions { shell } => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+            ExitCode::SUCCESS
+        }
+        Command::ExportPreset { output } => match settings(None).await {
+            Some(settings) => export_preset(settings, output).await,
+            None => ExitCode::FAILURE,
+        },
+        Command::ImportPreset { file } => import_preset(file).await,
+        Command::ExportReview { dir, output } => match settings(dir).await {
+            Some(settings) => export_review(settings, output).await,
+            None => ExitCode::FAILURE,
+        },
+        Command::ImportReview { file } => match settings(None).await {
+            Some(settings) => import_review(settings, &file).await,
+            None => ExitCode::FAILURE,
+        },
+    }
+}
+
+pub fn version(verbose: bool) -> ExitCode {
+    println!("lrcfetch {}", env!("CARGO_PKG_VERSION"));
+    if verbose {
+        let features = match env!("LRCFETCH_FEATURES") {
+            "" => "none",
+            features => features,
+        };
+        println!("commit: {}", env!("LRCFETCH_GIT_HASH"));
+        println!("features: {features}");
+        println!("target: {}", env!("LRCFETCH_TARGET"));
+    }
+    ExitCode::SUCCESS
+}
+
+pub fn man() -> ExitCode {
+    match clap_mangen::Man::new(Cli::command()).render(&mut std::io::stdout()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("lrcfetch: could not write the man page: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn settings(dir: Option<PathBuf>) -> Option<Settings> {
+    let Some(mut settings) = get_or_create_config().await else {
+        eprintln!("lrcfetch: could not load the configuration file");
+        return None;
+    };
+    if let Some(dir) = dir {
+        settings.music_path = dir;
+    }
+    Some(settings)
+}
+
+async fn library(settings: &Settings) -> Option<Vec<Arc<MusicData>>> {
+    if !settings.music_path.is_dir() {
+        eprintln!("lrcfetch: could not scan {}", settings.music_path.display());
+        return None;
+    }
+    let mut music = scan_music(settings).await;
+    apply_duration_overrides(&mut music, &store::load(DURATIONS_FILE).await);
+    Some(music)
+}
+
+async fn fetch(settings: Settings, force: bool, ignore_cooldown: bool) -> ExitCode {
+    let Some(music) = library(&settings).await else {
+        return ExitCode::FAILURE;
+    };
+    let local_limiter = Arc::new(Semaphore::new(settings.concurrent_local_reads));
+    let statuses = check_lyrics_all(&music, local_limiter).await;
+    let mut misses: Misses = store::load(MISSES_FILE).await;
+    let queue = music
+        .into_iter()
+        .filter(|data| data.duration == 0 || data.duration >= settings.min_duration)
+        .filter(|data| {
+            force
+                || matches!(
+                    statuses.get(&data.path),
+                    None | Some(LyricsStatus::None | LyricsStatus::Plain)
+                ) && (ignore_cooldown
+                    || !misses.cooling_down(&data.path, settings.miss_cooldown_hours))
+        })
+        .collect::<VecDeque<_>>();
+    let stats = fetch_tracks(&settings, queue, &mut misses, force || ignore_cooldown).await;
+    eprintln!("{stats}");
+    if stats.errors == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Fetches lyrics for one audio file, for import hooks in other tools. Unlike a library
+/// fetch it ignores the miss cooldown, since the file was asked for by name.
+async fn fetch_file(
+    settings: Settings,
+    file: PathBuf,
+    force: bool,
+    ignore_cooldown: bool,
+) -> ExitCode {
+    let data = match MusicData::from_file(file.clone(), &mut Interner::default()) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("lrcfetch: could not read {}: {err}", file.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut music = vec![Arc::new(data)];
+    apply_duration_overrides(&mut music, &store::load(DURATIONS_FILE).await);
+    let status = music[0].check_lyrics().await.unwrap_or(LyricsStatus::None);
+    if status == LyricsStatus::Synced && !force {
+        eprintln!("{}: already synced", file.display());
+        return ExitCode::SUCCESS;
+    }
+    let mut misses: Misses = store::load(MISSES_FILE).await;
+    let stats = fetch_tracks(
+        &settings,
+        music.into(),
+        &mut misses,
+        force || ignore_cooldown,
+    )
+    .await;
+    if stats.errors == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Runs `queue` through the providers, then saves the miss cooldowns and review queue.
+/// `bypass_cache` asks the providers again even where a cached response is still fresh.
+async fn fetch_tracks(
+    settings: &Settings,
+    queue: VecDeque<Arc<MusicData>>,
+    misses: &mut Misses,
+    bypass_cache: bool,
+) -> Stats {
+    let registry = registry(settings).await;
+    let mut review: Review = store::load(REVIEW_FILE).await;
+    let stats = run_queue(
+        settings,
+        queue,
+        &registry,
+        misses,
+        &mut review,
+        false,
+        bypass_cache,
+    )
+    .await;
+    if let Err(err) = store::save(MISSES_FILE, &*misses).await {
+        eprintln!("lrcfetch: could not save the miss cooldowns: {err}");
+    }
+    if let Err(err) = store::save(REVIEW_FILE, &review).await {
+        eprintln!("lrcfetch: could not save the review queue: {err}");
+    }
+    stats
+}
+
+async fn scan(settings: Settings) -> ExitCode {
+    if !settings.music_path.is_dir() {
+        eprintln!("lrcfetch: could not scan {}", settings.music_path.display());
+        return ExitCode::FAILURE;
+    }
+    let start = Instant::now();
+    let mut read = 0;
+    let music = scan_folder(
+        &settings,
+        &settings.music_path,
+        &ScanProgress::default(),
+        |_| {
+            read += 1;
+            if read % SCAN_PROGRESS_EVERY == 0 {
+                eprintln!("scan: {read} tracks read");
+            }
+        },
+    )
+    .await;
+    eprintln!(
+        "scan: indexed {} tracks in {:.1?}",
+        music.len(),
+        start.elapsed()
+    );
+    ExitCode::SUCCESS
+}
+
+async fn status(settings: Settings) -> ExitCode {
+    let Some(music) = library(&settings).await else {
+        return ExitCode::FAILURE;
+    };
+    let local_limiter = Arc::new(Semaphore::new(settings.concurrent_local_reads));
+    let statuses = check_lyrics_all(&music, local_limiter).await;
+    let coverage = Coverage::new(&music, &statuses);
+    let misses: Misses = store::load(MISSES_FILE).await;
+    let cooling_down = music
+        .iter()
+        .filter(|data| misses.cooling_down(&data.path, settings.miss_cooldown_hours))
+        .count();
+    let review: Review = store::load(REVIEW_FILE).await;
+    println!("tracks:        {}", coverage.tracks);
+    println!(
+        "synced:        {} ({:.0}%)",
+        coverage.synced, coverage.synced_percent
+    );
+    println!("plain:         {}", coverage.plain);
+    println!("instrumental:  {}", coverage.instrumental);
+    println!("missing:       {}", coverage.missing);
+    println!("to review:     {}", review.len());
+    println!("cooling down:  {cooling_down}");
+    ExitCode::SUCCESS
+}
+
+async fn registry(settings: &Settings) -> Arc<Registry> {
+    let throttle = Arc::new(Throttle::new(
+        settings.politeness_for("lrclib", &settings.lrclib_url),
+    ));
+    let cache = ResponseCache::for_settings(settings).await;
+    Arc::new(Registry::new(
+        settings,
+        &build_client(settings),
+        throttle,
+        cache,
+    ))
+}
+
+async fn run_queue(
+    settings: &Settings,
+    mut queue: VecDeque<Arc<MusicData>>,
+    registry: &Arc<Registry>,
+    misses: &mut Misses,
+    review: &mut Review,
+    synced_only: bool,
+    bypass_cache: bool,
+) -> Stats {
+    let limit = settings.concurrent_queries.max(1);
+    let total = queue.len();
+    let mut done = 0;
+    let mut stats = Stats::default();
+    let mut joins = JoinSet::new();
+    loop {
+        while joins.len() < registry.concurrency(limit)
+            && let Some(data) = queue.pop_front()
+        {
+            let registry = registry.clone();
+            let providers = settings.providers_for(&data).to_vec();
+            joins.spawn(async move {
+                let result = registry.fetch(&providers, &data, bypass_cache).await;
+                (data, result)
+            });
+        }
+        let Some(joined) = joins.join_next().await else {
+            break;
+        };
+        done += 1;
+        let Ok((data, result)) = joined else {
+            stats.errors += 1;
+            continue;
+        };
+        match result {
+            Ok(Fetched {
+                review: borderline, ..
+            }) if !borderline.is_empty() => {
+                stats.record_borderline();
+                eprintln!(
+                    "[{done}/{total}] {} - {}: needs review (best match scored {:.2})",
+                    data.artist, data.title, borderline[0].score
+                );
+                review.add(data.path.clone(), borderline);
+            }
+            Ok(Fetched {
+                lyrics, provider, ..
+            }) => {
+                stats.record_lyrics(&lyrics);
+                misses.record(&data.path, &lyrics);
+                review.remove(&data.path);
+                if !synced_only || lyrics.status() == LyricsStatus::Synced {
+                    let options = settings.write_options();
+                    let written = lyrics.write_checked(&data.path, &options).await;
+                    match &written {
+                        Ok(1..) => notify_players(&lyrics, &data.path, &options).await,
+                        Ok(0) => {}
+                        Err(err) => eprintln!("{}: {err}", data.path.display()),
+                    }
+                    stats.record_write(written);
+                }
+                let source = provider.map_or(String::new(), |name| format!(" ({name})"));
+                eprintln!(
+                    "[{done}/{total}] {} - {}: {}{source}",
+                    data.artist,
+                    data.title,
+                    lyrics.status()
+                );
+            }
+            Err(error) => {
+                stats.errors += 1;
+                eprintln!(
+                    "[{done}/{total}] {} - {}: error: {}",
+                    data.artist, data.title, error.reason
+                );
+            }
+        }
+    }
+    if let Err(err) = registry.save_cache().await {
+        eprintln!("lrcfetch: could not save the response cache: {err}");
+    }
+    stats.requests = registry.requests();
+    stats
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+async fn daemon(settings: Settings) -> ExitCode {
+    let registry = registry(&settings).await;
+    let local_limiter = Arc::new(Semaphore::new(settings.concurrent_local_reads));
+    let interval = Duration::from_secs(settings.refresh.interval_minutes.max(1) * 60);
+    let cooldown = settings.refresh.cooldown_hours * 60 * 60;
+    loop {
+        let Some(music) = library(&settings).await else {
+            return ExitCode::FAILURE;
+        };
+        let statuses = check_lyrics_all(&music, local_limiter.clone()).await;
+        let mut attempts: HashMap<PathBuf, u64> = store::load(REFRESH_FILE).await;
+        let now = unix_now();
+        attempts.retain(|path, _| statuses.contains_key(path));
+        let queue = music
+            .into_iter()
+            .filter(|data| data.duration >= settings.min_duration)
+            .filter(|data| statuses.get(&data.path) == Some(&LyricsStatus::Plain))
+            .filter(|data| {
+                attempts
+                    .get(&data.path)
+                    .is_none_or(|&attempted| now.saturating_sub(attempted) >= cooldown)
+            })
+            .take(settings.refresh.max_tracks)
+            .collect::<VecDeque<_>>();
+        eprintln!("refresh: checking {} plain-only tracks", queue.len());
+        for data in &queue {
+            attempts.insert(data.path.clone(), now);
+        }
+        let mut misses: Misses = store::load(MISSES_FILE).await;
+        let mut review: Review = store::load(REVIEW_FILE).await;
+        let stats = run_queue(
+            &settings,
+            queue,
+            &registry,
+            &mut misses,
+            &mut review,
+            true,
+            false,
+        )
+        .await;
+        if let Err(err) = store::save(MISSES_FILE, &misses).await {
+            eprintln!("lrcfetch: could not save the miss cooldowns: {err}");
+        }
+        if let Err(err) = store::save(REVIEW_FILE, &review).await {
+            eprintln!("lrcfetch: could not save the review queue: {err}");
+        }
+        if let Err(err) = store::save(REFRESH_FILE, &attempts).await {
+            eprintln!("lrcfetch: could not save refresh cooldowns: {err}");
+        }
+        eprintln!("refresh: {} upgraded to synced", stats.synced);
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// The `verify` checks, shared with the TUI's trash-and-refetch action. Timestamps in
+/// another format than `precision` only count when one is given, since a rewrite fixes them.
+pub fn sidecar_problems(
+    data: &MusicData,
+    lyrics: &Lyrics,
+    precision: Option<TimestampPrecision>,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    match lyrics {
+        Lyrics::Synced(text) => {
+            if let Some(precision) = precision
+                && lrc::normalize(text, precision) != *text
+            {
+                problems.push(format!("timestamps are not in {precision:?}"));
+            }
+            let report = lrc::validate(text);
+            if report.timed_lines == 0 {
+                problems.push("no timestamped lines".to_string());
+            }
+            if let Some(first) = report.invalid_lines.first() {
+                problems.push(format!(
+                    "{} malformed lines (first at line {first})",
+                    report.invalid_lines.len()
+                ));
+            }
+            if let Some(last) = report.last_timestamp
+                && data.duration > 0
+                && last.as_secs() > data.duration as u64 + 10
+            {
+                problems.push(format!(
+                    "last timestamp {}s is past the track length {}s",
+                    last.as_secs(),
+                    data.duration
+                ));
+            }
+        }
+        Lyrics::Plain(text) => {
+            if text.trim().is_empty() {
+                problems.push("empty lyrics file".to_string());
+            }
+        }
+        Lyrics::None | Lyrics::Instrumental => {}
+    }
+    problems
+}
+
+async fn verify(settings: Settings, fix: bool) -> ExitCode {
+    let precision = settings.timestamp_precision;
+    let Some(music) = library(&settings).await else {
+        return ExitCode::FAILURE;
+    };
+    let local_limiter = Arc::new(Semaphore::new(settings.concurrent_local_reads));
+    let statuses = check_lyrics_all(&music, local_limiter.clone()).await;
+    let mut joins = JoinSet::new();
+    for data in music {
+        let Some(&status) = statuses.get(&data.path) else {
+            continue;
+        };
+        let Some(sidecar) = status.sidecar(&data.path) else {
+            continue;
+        };
+        let semaphore = local_limiter.clone();
+        joins.spawn(async move {
+            let _lock = semaphore.acquire_owned().await;
+            // Embedded-only lyrics are read through the same status; fixing them would add a sidecar.
+            let on_disk = tokio::fs::try_exists(&sidecar).await.unwrap_or(false);
+            let lyrics = match data.read_lyrics(status).await {
+                Ok(lyrics) => lyrics,
+                Err(err) => return (data, vec![format!("unreadable: {err}")]),
+            };
+            let mut problems = sidecar_problems(&data, &lyrics, Some(precision));
+            if fix
+                && on_disk
+                && let Lyrics::Synced(text) = &lyrics
+                && lrc::normalize(text, precision) != *text
+            {
+                let normalized = Lyrics::Synced(lrc::normalize(text, precision));
+                problems.push(match normalized.to_file(&data.path).await {
+                    Ok(_) => format!("rewrote timestamps as {precision:?}"),
+                    Err(err) => format!("could not rewrite timestamps: {err}"),
+                });
+            }
+            (data, problems)
+        });
+    }
+    let mut checked = 0;
+    let mut flagged = 0;
+    while let Some(Ok((data, problems))) = joins.join_next().await {
+        checked += 1;
+        if problems.is_empty() {
+            continue;
+        }
+        flagged += 1;
+        for problem in problems {
+            println!("{}: {problem}", data.path.display());
+        }
+    }
+    eprintln!("checked {checked} sidecars, {flagged} with problems");
+    if flagged == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+async fn export_preset(settings: Settings, output: Option<PathBuf>) -> ExitCode {
+    let preset: Preset = store::load(PRESET_FILE).await;
+    let preset = preset.export(settings.theme);
+    let ron = ron::ser::to_string_pretty(&preset, ron::ser::PrettyConfig::default()).unwrap();
+    match output {
+        Some(path) => {
+            if let Err(err) = tokio::fs::write(&path, ron).await {
+                eprintln!("lrcfetch: could not write {}: {err}", path.display());
+                return ExitCode::FAILURE;
+            }
+            eprintln!(
+                "exported {} bindings to {}",
+                preset.keys.len(),
+                path.display()
+            );
+        }
+        None => println!("{ron}"),
+    }
+    ExitCode::SUCCESS
+}
+
+async fn import_preset(file: PathBuf) -> ExitCode {
+    let contents = match tokio::fs::read_to_string(&file).await {
+        Ok(contents) => contents,
+        Err(err) => {
+            eprintln!("lrcfetch: could not read {}: {err}", file.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let preset = match ron::from_str::<Preset>(&contents) {
+        Ok(preset) => preset,
+        Err(err) => {
+            eprintln!("lrcfetch: {} is not a valid preset: {err}", file.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let (_, errors) = preset.keymap();
+    if !errors.is_empty() {
+        for err in errors {
+            eprintln!("lrcfetch: {}: {err}", file.display());
+        }
+        return ExitCode::FAILURE;
+    }
+    if let Err(err) = store::save(PRESET_FILE, &preset).await {
+        eprintln!("lrcfetch: could not save the preset: {err}");
+        return ExitCode::FAILURE;
+    }
+    eprintln!(
+        "imported {} bindings from {}",
+        preset.keys.len(),
+        file.display()
+    );
+    ExitCode::SUCCESS
+}
+
+async fn export_review(settings: Settings, output: Option<PathBuf>) -> ExitCode {
+    let Some(music) = library(&settings).await else {
+        return ExitCode::FAILURE;
+    };
+    let review: Review = store::load(REVIEW_FILE).await;
+    let entries = review.export(&music);
+    let json = serde_json::to_string_pretty(&entries).unwrap();
+    match output {
+        Some(path) => {
+            if let Err(err) = tokio::fs::write(&path, json).await {
+                eprintln!("lrcfetch: could not write {}: {err}", path.display());
+                return ExitCode::FAILURE;
+            }
+            eprintln!("exported {} tracks to {}", entries.len(), path.display());
+        }
+        None => println!("{json}"),
+    }
+    ExitCode::SUCCESS
+}
+
+async fn import_review(settings: Settings, file: &Path) -> ExitCode {
+    let entries = match tokio::fs::read_to_string(file)
+        .await
+        .map(|json| serde_json::from_str::<Vec<ReviewEntry>>(&json))
+    {
+        Ok(Ok(entries)) => entries,
+        Ok(Err(err)) => {
+            eprintln!("lrcfetch: invalid review export {}: {err}", file.display());
+            return ExitCode::FAILURE;
+        }
+        Err(err) => {
+            eprintln!("lrcfetch: could not read {}: {err}", file.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut review: Review = store::load(REVIEW_FILE).await;
+    let mut stats = Stats::default();
+    let (mut accepted, mut rejected, mut undecided) = (0, 0, 0);
+    for entry in entries {
+        // The file may come from someone else, so only its decisions are used: the track must
+        // already be queued here, and the lyrics come from the locally stored candidates.
+        let Some(candidates) = review
+            .get(&entry.path)
+            .filter(|_| in_library(&settings, &entry.path))
+        else {
+            if entry.decision.is_some() {
+                eprintln!("{}: not in the review queue", entry.path.display());
+                stats.errors += 1;
+            }
+            continue;
+        };
+        match entry.decision {
+            None => undecided += 1,
+            Some(Decision::Reject) => {
+                review.remove(&entry.path);
+                rejected += 1;
+            }
+            Some(Decision::Accept(id)) => {
+                let Some(lyrics) = candidates
+                    .iter()
+                    .find(|scored| scored.candidate.id == id)
+                    .map(|scored| scored.candidate.lyrics())
+                else {
+                    eprintln!("{}: no candidate with id {id}", entry.path.display());
+                    stats.errors += 1;
+                    continue;
+                };
+                let options = settings.write_options();
+                let written = lyrics.write_checked(&entry.path, &options).await;
+                match &written {
+                    Ok(bytes) => {
+                        if *bytes > 0 {
+                            notify_players(&lyrics, &entry.path, &options).await;
+                        }
+                        review.remove(&entry.path);
+                        accepted += 1;
+                    }
+                    Err(err) => eprintln!("{}: {err}", entry.path.display()),
+                }
+                stats.record_write(written);
+            }
+        }
+    }
+    if let Err(err) = store::save(REVIEW_FILE, &review).await {
+        eprintln!("lrcfetch: could not save the review queue: {err}");
+        return ExitCode::FAILURE;
+    }
+    eprintln!(
+        "accepted {accepted}, rejected {rejected}, {undecided} undecided, {} errors",
+        stats.errors
+    );
+    if stats.errors == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+async fn export(settings: Settings, output: Option<PathBuf>) -> ExitCode {
+    let Some(music) = library(&settings).await else {
+        return ExitCode::FAILURE;
+    };
+    let local_limiter = Arc::new(Semaphore::new(settings.concurrent_local_reads));
+    let statuses = check_lyrics_all(&music, local_limiter).await;
+    let flags: HashMap<PathBuf, TrackFlag> = store::load(FLAGS_FILE).await;
+    let mut entries = Vec::with_capacity(music.len());
+    for data in music {
+        let status = statuses
+            .get(&data.path)
+            .copied()
+            .unwrap_or(LyricsStatus::None);
+        let lyrics = data.read_lyrics(status).await.unwrap_or(Lyrics::None);
+        let flag = flags.get(&data.path).copied();
+        entries.push(LibraryEntry::new(&data, &lyrics, flag));
+    }
+    let json = serde_json::to_string_pretty(&entries).unwrap();
+    match output {
+        Some(path) => {
+            if let Err(err) = tokio::fs::write(&path, json).await {
+                eprintln!("lrcfetch: could not write {}: {err}", path.display());
+                return ExitCode::FAILURE;
+            }
+            eprintln!("exported {} tracks to {}", entries.len(), path.display());
+        }
+        None => println!("{json}"),
+    }
+    ExitCode::SUCCESS
+}
+
+async fn badge(settings: Settings, format: BadgeFormat, output: Option<PathBuf>) -> ExitCode {
+    let Some(music) = library(&settings).await else {
+        return ExitCode::FAILURE;
+    };
+    let local_limiter = Arc::new(Semaphore::new(settings.concurrent_local_reads));
+    let statuses = check_lyrics_all(&music, local_limiter).await;
+    let coverage = Coverage::new(&music, &statuses);
+    let contents = match format {
+        BadgeFormat::Json => serde_json::to_string_pretty(&coverage).unwrap(),
+        BadgeFormat::Svg => coverage.to_svg(),
+    };
+    match output {
+        Some(path) => {
+            if let Err(err) = tokio::fs::write(&path, contents).await {
+                eprintln!("lrcfetch: could not write {}: {err}", path.display());
+                return ExitCode::FAILURE;
+            }
+        }
+        None => println!("{contents}"),
+    }
+    ExitCode::SUCCESS
+}
+
+async fn playlist(
+    settings: Settings,
+    lyrics: PlaylistLyrics,
+    filter: Filter,
+    output: Option<PathBuf>,
+) -> ExitCode {
+    let Some(music) = library(&settings).await else {
+        return ExitCode::FAILURE;
+    };
+    let local_limiter = Arc::new(Semaphore::new(settings.concurrent_local_reads));
+    let statuses = check_lyrics_all(&music, local_limiter).await;
+    let keys = filter.keys();
+    let tracks = music
+        .iter()
+        .filter(|data| keys.matches(data))
+        .filter(|data| lyrics.matches(statuses.get(&data.path).copied()))
+        .map(|data| &**data)
+        .collect::<Vec<_>>();
+    let contents = to_m3u(tracks.iter().copied());
+    match output {
+        Some(path) => {
+            if let Err(err) = tokio::fs::write(&path, contents).await {
+                eprintln!("lrcfetch: could not write {}: {err}", path.display());
+                return ExitCode::FAILURE;
+            }
+            eprintln!("wrote {} tracks to {}", tracks.len(), path.display());
+        }
+        None => print!("{contents}"),
+    }
+    ExitCode::SUCCESS
+}
+
+async fn extract(settings: Settings, overwrite: bool) -> ExitCode {
+    let Some(music) = library(&settings).await else {
+        return ExitCode::FAILURE;
+    };
+    let local_limiter = Arc::new(Semaphore::new(settings.concurrent_local_reads));
+    let mut joins = JoinSet::new();
+    for data in music {
+        if data.embedded.sidecar(&data.path).is_none() {
+            continue;
+        }
+        let semaphore = local_limiter.clone();
+        joins.spawn(async move {
+            let _lock = semaphore.acquire_owned().await;
+            let written = data.extract_embedded(overwrite).await;
+            (data.path.clone(), written)
+        });
+    }
+    let mut stats = Stats::default();
+    let mut extracted = 0;
+    while let Some(Ok((path, result))) = joins.join_next().await {
+        match &result {
+            Ok(0) => {}
+            Ok(_) => extracted += 1,
+            Err(err) => eprintln!("{}: {err}", path.display()),
+        }
+        stats.record_write(result);
+    }
+    eprintln!(
+        "extracted {extracted} sidecars ({:.1} KiB), {} errors",
+        stats.bytes_written as f64 / 1024.0,
+        stats.errors
+    );
+    if stats.errors == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Runs the player hooks for freshly written lyrics. A failing hook is reported on its own,
+/// since the lyrics are saved either way.
+async fn notify_players(lyrics: &Lyrics, path: &Path, options: &WriteOptions) {
+    if let Err(err) = lyrics.notify_players(path, options).await {
+        eprintln!("{}: player hook failed: {err}", path.display());
+    }
+}
+
+/// Whether `path` is inside the library, so an imported file can't write lyrics elsewhere.
+fn in_library(settings: &Settings, path: &Path) -> bool {
+    path.starts_with(&settings.music_path)
+        && !path
+            .components()
+            .any(|component| component == Component::ParentDir)
+}
+
+async fn import(settings: Settings, file: &Path, overwrite: bool) -> ExitCode {
+    let entries = match tokio::fs::read_to_string(file)
+        .await
+        .map(|json| serde_json::from_str::<Vec<LibraryEntry>>(&json))
+    {
+        Ok(Ok(entries)) => entries,
+        Ok(Err(err)) => {
+            eprintln!("lrcfetch: invalid export {}: {err}", file.display());
+            return ExitCode::FAILURE;
+        }
+        Err(err) => {
+            eprintln!("lrcfetch: could not read {}: {err}", file.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let local_limiter = Arc::new(Semaphore::new(settings.concurrent_local_reads));
+    let mut joins = JoinSet::new();
+    let mut stats = Stats::default();
+    for entry in entries {
+        let options = settings.write_options();
+        let lyrics = entry.lyrics();
+        if let Lyrics::None = lyrics {
+            continue;
+        }
+        if !in_library(&settings, &entry.path) {
+            eprintln!("{}: outside the music directory", entry.path.display());
+            stats.errors += 1;
+            continue;
+        }
+        let semaphore = local_limiter.clone();
+        joins.spawn(async move {
+            let _lock = semaphore.acquire_owned().await;
+            let sidecar = lyrics.status().sidecar(&entry.path);
+            if !overwrite
+                && let Some(sidecar) = sidecar
+                && tokio::fs::try_exists(&sidecar).await.unwrap_or(false)
+            {
+                return (entry.path, Ok(0));
+            }
+            let written = lyrics.write(&entry.path, &options).await;
+            if let Ok(1..) = written {
+                notify_players(&lyrics, &entry.path, &options).await;
+            }
+            (entry.path, written)
+        });
+    }
+    while let Some(Ok((path, result))) = joins.join_next().await {
+        if let Err(err) = &result {
+            eprintln!("{}: {err}", path.display());
+        }
+        stats.record_write(result);
+    }
+    eprintln!(
+        "imported {:.1} KiB of lyrics, {} errors",
+        stats.bytes_written as f64 / 1024.0,
+        stats.errors
+    );
+    if stats.errors == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+async fn show(file: PathBuf) -> ExitCode {
+    let data = match MusicData::from_file(file.clone(), &mut Interner::default()) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("lrcfetch: could not read {}: {err}", file.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    println!("Title:    {}", data.title);
+    println!("Artist:   {}", data.artist);
+    println!("Album:    {}", data.album);
+    println!("Duration: {}s", data.duration);
+    let status = data.check_lyrics().await.unwrap_or(LyricsStatus::None);
+    println!("Lyrics:   {status}");
+    match data.read_lyrics(status).await {
+        Ok(Lyrics::Synced(text) | Lyrics::Plain(text)) => println!("\n{text}"),
+        Ok(_) => {}
+        Err(err) => eprintln!("lrcfetch: could not read lyrics: {err}"),
+    }
+    ExitCode::SUCCESS
+}
+
+async fn bench(settings: Settings) -> ExitCode {
+    let start = Instant::now();
+    let Some(music) = library(&settings).await else {
+        return ExitCode::FAILURE;
+    };
+    let scanned = start.elapsed();
+    let start = Instant::now();
+    let local_limiter = Arc::new(Semaphore::new(settings.concurrent_local_reads));
+    let statuses = check_lyrics_all(&music, local_limiter).await;
+    let checked = start.elapsed();
+    println!("scan + tags:  {} tracks in {scanned:.2?}", music.len());
+    println!("lyric check:  {} tracks in {checked:.2?}", statuses.len());
+    ExitCode::SUCCESS
+}
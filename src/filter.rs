@@ -1 +1,150 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
 
+use ratatui::{text::Text, widgets::List};
+
+use crate::library::TrackFlag;
+use crate::musicdata::{MusicData, fold};
+
+#[derive(Default, Clone, PartialEq, Eq)]
+pub struct Filter {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub min_duration: usize,
+    pub include_short: bool,
+    /// Only tracks with this curation flag; checked by the caller, which owns the flags.
+    pub flag: Option<TrackFlag>,
+    pub added_within_days: Option<u64>,
+    /// Only tracks at or below this directory.
+    pub folder: Option<PathBuf>,
+}
+
+impl Filter {
+    pub fn keys(&self) -> FilterKeys {
+        FilterKeys {
+            title: self.title.as_deref().map(fold),
+            artist: self.artist.as_deref().map(fold),
+            album: self.album.as_deref().map(fold),
+            min_duration: if self.include_short {
+                0
+            } else {
+                self.min_duration
+            },
+            // A span reaching before the clock's epoch has no lower bound.
+            added_after: self.added_within_days.and_then(|days| {
+                SystemTime::now()
+                    .checked_sub(Duration::from_secs(days.saturating_mul(24 * 60 * 60)))
+            }),
+            folder: self.folder.clone(),
+        }
+    }
+    pub fn narrows(&self, previous: &Filter) -> bool {
+        fn field_narrows(current: &Option<String>, previous: &Option<String>) -> bool {
+            match (current, previous) {
+                (_, None) => true,
+                (None, Some(_)) => false,
+                (Some(current), Some(previous)) => fold(current).contains(&fold(previous)),
+            }
+        }
+        (previous.include_short || !self.include_short)
+            && (previous.flag.is_none() || self.flag == previous.flag)
+            && previous
+                .added_within_days
+                .is_none_or(|previous| self.added_within_days.is_some_and(|days| days <= previous))
+            && previous.folder.as_ref().is_none_or(|previous| {
+                self.folder
+                    .as_ref()
+                    .is_some_and(|folder| folder.starts_with(previous))
+            })
+            && self.min_duration == previous.min_duration
+            && field_narrows(&self.title, &previous.title)
+            && field_narrows(&self.artist, &previous.artist)
+            && field_narrows(&self.album, &previous.album)
+    }
+    pub fn to_widget(&self) -> List<'_> {
+        let mut list = Vec::new();
+        if let Some(title) = &self.title {
+            list.push(Text::raw(format!("Title: {}", title)).centered());
+        } else {
+            list.push(Text::raw("Title:".to_string()).centered());
+        }
+        if let Some(artist) = &self.artist {
+            list.push(Text::raw(format!("Artist: {}", artist)).centered());
+        } else {
+            list.push(Text::raw("Artist:".to_string()).centered());
+        }
+        if let Some(album) = &self.album {
+            list.push(Text::raw(format!("Album: {}", album)).centered());
+        } else {
+            list.push(Text::raw("Album:".to_string()).centered());
+        }
+        if self.include_short {
+            list.push(Text::raw("Short tracks: shown").centered());
+        } else {
+            list.push(
+                Text::raw(format!("Short tracks: hidden (<{}s)", self.min_duration)).centered(),
+            );
+        }
+        match self.flag {
+            Some(flag) => list.push(Text::raw(format!("Flag: {flag}")).centered()),
+            None => list.push(Text::raw("Flag: any").centered()),
+        }
+        match self.added_within_days {
+            Some(days) => list.push(Text::raw(format!("Added: last {days} days")).centered()),
+            None => list.push(Text::raw("Added: any time").centered()),
+        }
+        match &self.folder {
+            Some(folder) => {
+                list.push(Text::raw(format!("Folder: {}", folder.display())).centered())
+            }
+            None => list.push(Text::raw("Folder: any").centered()),
+        }
+        List::new(list)
+    }
+}
+
+pub struct FilterKeys {
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    min_duration: usize,
+    added_after: Option<SystemTime>,
+    folder: Option<PathBuf>,
+}
+
+impl FilterKeys {
+    pub fn matches(&self, item: &MusicData) -> bool {
+        // An unknown length (0) is not a short track; it stays visible so it can be flagged.
+        if item.duration != 0 && item.duration < self.min_duration {
+            return false;
+        }
+        if self.added_after.is_some_and(|after| item.added < after) {
+            return false;
+        }
+        if let Some(folder) = &self.folder
+            && !item.path.starts_with(folder)
+        {
+            return false;
+        }
+        if let Some(album) = &self.album
+            && !item.keys.album.contains(album.as_str())
+        {
+            return false;
+        }
+        if let Some(artist) = &self.artist
+            && !item.keys.artist.contains(artist.as_str())
+            && !item.group_key().contains(artist.as_str())
+        {
+            return false;
+        }
+        if let Some(title) = &self.title
+            && !item.keys.title.contains(title.as_str())
+        {
+            return false;
+        }
+        true
+    }
+}
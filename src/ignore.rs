@@ -0,0 +1,193 @@
+use std::path::{Path, PathBuf};
+
+pub const IGNORE_FILE: &str = ".lrcfetchignore";
+
+struct Rule {
+    pattern: Vec<char>,
+    negated: bool,
+    dir_only: bool,
+    /// Matched against the whole path from the root rather than just the file name.
+    anchored: bool,
+}
+
+/// Paths the scanner skips, from a gitignore-style file at the music root. Supports `#`
+/// comments, `!` negation, trailing `/` for directories, leading `/` to anchor, and the `*`,
+/// `?` and `**` wildcards. As in git, a file under an ignored directory cannot be re-included.
+#[derive(Default)]
+pub struct Ignore {
+    root: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl Ignore {
+    pub fn load(root: &Path) -> Self {
+        let text = std::fs::read_to_string(root.join(IGNORE_FILE)).unwrap_or_default();
+        Self::parse(root, &text)
+    }
+
+    pub fn parse(root: &Path, text: &str) -> Self {
+        let rules = text
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (negated, line) = match line.strip_prefix('!') {
+                    Some(line) => (true, line),
+                    None => (false, line.strip_prefix('\\').unwrap_or(line)),
+                };
+                let (dir_only, line) = match line.strip_suffix('/') {
+                    Some(line) => (true, line),
+                    None => (false, line),
+                };
+                let anchored = line.contains('/');
+                Rule {
+                    pattern: line.trim_start_matches('/').chars().collect(),
+                    negated,
+                    dir_only,
+                    anchored,
+                }
+            })
+            .collect();
+        Self {
+            root: root.to_path_buf(),
+            rules,
+        }
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        let relative = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+            .chars()
+            .collect::<Vec<_>>();
+        let name = match relative.iter().rposition(|&c| c == '/') {
+            Some(slash) => &relative[slash + 1..],
+            None => &relative[..],
+        };
+        let mut ignored = false;
+        for rule in &self.rules {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+            let target = if rule.anchored { &relative[..] } else { name };
+            if glob(&rule.pattern, target) {
+                ignored = !rule.negated;
+            }
+        }
+        ignored
+    }
+}
+
+/// `*` and `?` stay within one path segment; `**` spans any number of them.
+fn glob(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => {
+            let rest = match rest {
+                ['/', after @ ..] if glob(after, text) => return true,
+                rest => rest,
+            };
+            (0..=text.len()).any(|skip| glob(rest, &text[skip..]))
+        }
+        ['*', rest @ ..] => {
+            let segment = text.iter().position(|&c| c == '/').unwrap_or(text.len());
+            (0..=segment).any(|skip| glob(rest, &text[skip..]))
+        }
+        ['?', rest @ ..] => text.first().is_some_and(|&c| c != '/') && glob(rest, &text[1..]),
+        [c, rest @ ..] => text.first() == Some(c) && glob(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ignore(rules: &str) -> Ignore {
+        Ignore::parse(Path::new("/music"), rules)
+    }
+
+    fn file(ignore: &Ignore, path: &str) -> bool {
+        ignore.is_ignored(&Path::new("/music").join(path), false)
+    }
+
+    fn dir(ignore: &Ignore, path: &str) -> bool {
+        ignore.is_ignored(&Path::new("/music").join(path), true)
+    }
+
+    #[test]
+    fn comments_and_blank_lines() {
+        let rules = ignore("# comment\n\n   \n\\#literal\n");
+        assert!(!file(&rules, "comment"));
+        assert!(file(&rules, "#literal"));
+    }
+
+    #[test]
+    fn unanchored_matches_any_depth() {
+        let rules = ignore("*.cue\nScans");
+        assert!(file(&rules, "album.cue"));
+        assert!(file(&rules, "Artist/Album/album.cue"));
+        assert!(dir(&rules, "Artist/Album/Scans"));
+        assert!(!file(&rules, "Artist/Album/01.flac"));
+        assert!(!file(&rules, "cue"));
+    }
+
+    #[test]
+    fn anchored_matches_from_the_root() {
+        let rules = ignore("/Incoming\nArtist/Live");
+        assert!(dir(&rules, "Incoming"));
+        assert!(!dir(&rules, "Artist/Incoming"));
+        assert!(dir(&rules, "Artist/Live"));
+        assert!(!dir(&rules, "Other/Artist/Live"));
+    }
+
+    #[test]
+    fn single_star_stays_in_one_segment() {
+        let rules = ignore("/Artist/*.flac\n?.mp3");
+        assert!(file(&rules, "Artist/01.flac"));
+        assert!(!file(&rules, "Artist/Album/01.flac"));
+        assert!(file(&rules, "Artist/a.mp3"));
+        assert!(!file(&rules, "Artist/ab.mp3"));
+    }
+
+    #[test]
+    fn double_star_spans_directories() {
+        let rules = ignore("**/Demos\n/Bootlegs/**\n/a/**/b.flac");
+        assert!(dir(&rules, "Demos"));
+        assert!(dir(&rules, "Artist/Album/Demos"));
+        assert!(file(&rules, "Bootlegs/1999/01.flac"));
+        assert!(!file(&rules, "Artist/Bootlegs/01.flac"));
+        assert!(file(&rules, "a/b.flac"));
+        assert!(file(&rules, "a/x/y/b.flac"));
+        assert!(!file(&rules, "a/xb.flac"));
+    }
+
+    #[test]
+    fn trailing_slash_only_matches_directories() {
+        let rules = ignore("Extras/");
+        assert!(dir(&rules, "Artist/Extras"));
+        assert!(!file(&rules, "Artist/Extras"));
+    }
+
+    #[test]
+    fn negation_reincludes_and_last_rule_wins() {
+        let rules = ignore("*.flac\n!keep.flac");
+        assert!(file(&rules, "Artist/drop.flac"));
+        assert!(!file(&rules, "Artist/keep.flac"));
+        let rules = ignore("!keep.flac\n*.flac");
+        assert!(file(&rules, "Artist/keep.flac"));
+    }
+
+    #[test]
+    fn paths_outside_the_root_are_kept() {
+        assert!(!Ignore::default().is_ignored(Path::new("/music/01.flac"), false));
+        assert!(!ignore("*").is_ignored(Path::new("/other/01.flac"), false));
+    }
+}
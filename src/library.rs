@@ -0,0 +1,610 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{sync::Semaphore, task::JoinSet};
+
+use crate::ignore::Ignore;
+use crate::musicdata::{
+    Interner, Lyrics, LyricsStatus, MusicData, SearchKeys, detect_compilations, fold,
+};
+use crate::settings::Settings;
+use crate::store;
+
+pub const DURATIONS_FILE: &str = "durations.ron";
+pub const FLAGS_FILE: &str = "flags.ron";
+pub const INDEX_FILE: &str = "library.ron";
+pub const MUSIC_EXTENSIONS: [&str; 7] = ["flac", "mp3", "m4a", "ogg", "opus", "wv", "ape"];
+
+/// A curation verdict on a track's lyrics, set by hand in the TUI.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackFlag {
+    Good,
+    NeedsReview,
+    Wrong,
+}
+
+impl TrackFlag {
+    /// The next flag when cycling, ending back at no flag.
+    pub fn cycle(flag: Option<TrackFlag>) -> Option<TrackFlag> {
+        match flag {
+            None => Some(TrackFlag::Good),
+            Some(TrackFlag::Good) => Some(TrackFlag::NeedsReview),
+            Some(TrackFlag::NeedsReview) => Some(TrackFlag::Wrong),
+            Some(TrackFlag::Wrong) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for TrackFlag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TrackFlag::Good => "good",
+            TrackFlag::NeedsReview => "needs review",
+            TrackFlag::Wrong => "wrong",
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LibraryEntry {
+    pub path: PathBuf,
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub duration: usize,
+    #[serde(default)]
+    pub synced: Option<String>,
+    #[serde(default)]
+    pub plain: Option<String>,
+    #[serde(default)]
+    pub flag: Option<TrackFlag>,
+}
+
+impl LibraryEntry {
+    pub fn new(data: &MusicData, lyrics: &Lyrics, flag: Option<TrackFlag>) -> Self {
+        let (synced, plain) = match lyrics {
+            Lyrics::Synced(text) => (Some(text.clone()), None),
+            Lyrics::Plain(text) => (None, Some(text.clone())),
+            Lyrics::None | Lyrics::Instrumental => (None, None),
+        };
+        Self {
+            path: data.path.clone(),
+            title: data.title.clone(),
+            artist: data.artist.to_string(),
+            album: data.album.to_string(),
+            duration: data.duration,
+            synced,
+            plain,
+            flag,
+        }
+    }
+
+    pub fn lyrics(&self) -> Lyrics {
+        if let Some(text) = &self.synced {
+            Lyrics::Synced(text.clone())
+        } else if let Some(text) = &self.plain {
+            Lyrics::Plain(text.clone())
+        } else {
+            Lyrics::None
+        }
+    }
+}
+
+/// Extended M3U with one `#EXTINF` line per track, so players show names without tags.
+pub fn to_m3u<'a>(music: impl IntoIterator<Item = &'a MusicData>) -> String {
+    let mut playlist = "#EXTM3U\n".to_string();
+    for data in music {
+        playlist.push_str(&format!(
+            "#EXTINF:{},{} - {}\n{}\n",
+            data.duration,
+            data.artist,
+            data.title,
+            data.path.display()
+        ));
+    }
+    playlist
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct Coverage {
+    pub tracks: usize,
+    pub synced: usize,
+    pub plain: usize,
+    pub instrumental: usize,
+    pub missing: usize,
+    pub synced_percent: f64,
+}
+
+impl Coverage {
+    pub fn new(music: &[Arc<MusicData>], statuses: &HashMap<PathBuf, LyricsStatus>) -> Self {
+        let mut coverage = Coverage {
+            tracks: music.len(),
+            ..Default::default()
+        };
+        for data in music {
+            match statuses.get(&data.path) {
+                Some(LyricsStatus::Synced) => coverage.synced += 1,
+                Some(LyricsStatus::Plain) => coverage.plain += 1,
+                Some(LyricsStatus::Instrumental) => coverage.instrumental += 1,
+                Some(LyricsStatus::None) | None => coverage.missing += 1,
+            }
+        }
+        if coverage.tracks > 0 {
+            coverage.synced_percent = coverage.synced as f64 * 100.0 / coverage.tracks as f64;
+        }
+        coverage
+    }
+
+    pub fn to_svg(&self) -> String {
+        let label = "lyrics";
+        let value = format!("{:.0}% synced of {}", self.synced_percent, self.tracks);
+        let color = match self.synced_percent {
+            percent if percent >= 90.0 => "#4c1",
+            percent if percent >= 70.0 => "#97ca00",
+            percent if percent >= 50.0 => "#dfb317",
+            percent if percent >= 25.0 => "#fe7d37",
+            _ => "#e05d44",
+        };
+        // Rough Verdana 11px advance, good enough for a static badge.
+        let label_width = label.len() * 7 + 10;
+        let value_width = value.len() * 7 + 10;
+        let width = label_width + value_width;
+        format!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {value}">
+<rect width="{label_width}" height="20" fill="#555"/>
+<rect x="{label_width}" width="{value_width}" height="20" fill="{color}"/>
+<g fill="#fff" text-anchor="middle" font-family="Verdana,DejaVu Sans,sans-serif" font-size="11">
+<text x="{}" y="14">{label}</text>
+<text x="{}" y="14">{value}</text>
+</g>
+</svg>
+"##,
+            label_width / 2,
+            label_width + value_width / 2,
+        )
+    }
+}
+
+/// How far a library scan has got, updated while it runs.
+#[derive(Default)]
+pub struct ScanProgress {
+    pub discovered: AtomicUsize,
+    pub loaded: AtomicUsize,
+    pub checked: AtomicUsize,
+}
+
+pub async fn check_lyrics_all(
+    music: &[Arc<MusicData>],
+    limiter: Arc<Semaphore>,
+) -> HashMap<PathBuf, LyricsStatus> {
+    check_lyrics_counted(music, limiter, &AtomicUsize::new(0)).await
+}
+
+/// Like `check_lyrics_all`, counting finished checks in `checked`.
+pub async fn check_lyrics_counted(
+    music: &[Arc<MusicData>],
+    limiter: Arc<Semaphore>,
+    checked: &AtomicUsize,
+) -> HashMap<PathBuf, LyricsStatus> {
+    let mut joinset = JoinSet::new();
+    for music in music.iter() {
+        let path = music.path.clone();
+        let music = Arc::clone(music);
+        let semaphore = limiter.clone();
+        joinset.spawn(async move {
+            let _lock = semaphore.acquire_owned().await;
+            (path, music.check_lyrics().await)
+        });
+    }
+    let mut statuses = HashMap::new();
+    while let Some(result) = joinset.join_next().await {
+        checked.fetch_add(1, Ordering::Relaxed);
+        if let Ok((path, Ok(lyrics))) = result {
+            statuses.insert(path, lyrics);
+        }
+    }
+    statuses
+}
+
+/// Recomputes a track's length by walking every packet in the stream
+/// instead of trusting the header, for files with damaged STREAMINFO.
+#[cfg(feature = "decode")]
+pub fn decode_duration(path: &Path) -> Option<usize> {
+    use symphonia::core::{
+        formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint,
+        units::TimeBase,
+    };
+
+    let file = std::fs::File::open(path).ok()?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(&ext.to_ascii_lowercase());
+    }
+    let mut format = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .ok()?
+        .format;
+    let track = format.default_track()?;
+    let track_id = track.id;
+    let time_base = track.codec_params.time_base.or_else(|| {
+        track
+            .codec_params
+            .sample_rate
+            .map(|rate| TimeBase::new(1, rate))
+    })?;
+    let mut frames = 0;
+    while let Ok(packet) = format.next_packet() {
+        if packet.track_id() == track_id {
+            frames += packet.dur;
+        }
+    }
+    Some(time_base.calc_time(frames).seconds as usize)
+}
+
+#[cfg(not(feature = "decode"))]
+pub fn decode_duration(_path: &Path) -> Option<usize> {
+    None
+}
+
+pub fn apply_duration_overrides(music: &mut [Arc<MusicData>], overrides: &HashMap<PathBuf, usize>) {
+    for data in music.iter_mut() {
+        if let Some(&duration) = overrides.get(&data.path) {
+            Arc::make_mut(data).duration = duration;
+        }
+    }
+}
+
+/// Guesses the extension of a file from its leading magic bytes.
+fn sniff_extension(path: &Path) -> Option<&'static str> {
+    let mut magic = [0; 12];
+    std::fs::File::open(path)
+        .ok()?
+        .read_exact(&mut magic)
+        .ok()?;
+    match magic {
+        [b'f', b'L', b'a', b'C', ..] => Some("flac"),
+        [b'I', b'D', b'3', ..] => Some("mp3"),
+        [0xff, second, ..] if second & 0xe0 == 0xe0 => Some("mp3"),
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => Some("m4a"),
+        [b'O', b'g', b'g', b'S', ..] => Some("ogg"),
+        [b'w', b'v', b'p', b'k', ..] => Some("wv"),
+        [b'M', b'A', b'C', b' ', ..] => Some("ape"),
+        _ => None,
+    }
+}
+
+fn is_music(path: &Path, settings: &Settings) -> bool {
+    let extensions = &settings.music_extensions;
+    match path.extension() {
+        Some(ext) => extensions
+            .iter()
+            .any(|extension| ext.eq_ignore_ascii_case(extension)),
+        None if settings.sniff_extensionless => sniff_extension(path).is_some_and(|ext| {
+            extensions
+                .iter()
+                .any(|extension| extension.eq_ignore_ascii_case(ext))
+        }),
+        None => false,
+    }
+}
+
+/// A track's tags as last read, reused while the file's modification time is unchanged.
+#[derive(Serialize, Deserialize, Clone)]
+struct IndexedTrack {
+    modified: SystemTime,
+    title: String,
+    artist: String,
+    album: String,
+    duration: usize,
+    disc: Option<u32>,
+    track: Option<u32>,
+    album_artist: Option<String>,
+    compilation: bool,
+    embedded: LyricsStatus,
+    added: SystemTime,
+}
+
+impl IndexedTrack {
+    fn new(data: &MusicData, modified: SystemTime) -> Self {
+        Self {
+            modified,
+            title: data.title.clone(),
+            artist: data.artist.to_string(),
+            album: data.album.to_string(),
+            duration: data.duration,
+            disc: data.disc,
+            track: data.track,
+            album_artist: data.album_artist.as_deref().map(str::to_string),
+            compilation: data.compilation,
+            embedded: data.embedded,
+            added: data.added,
+        }
+    }
+
+    fn to_music(&self, path: PathBuf, interner: &mut Interner) -> MusicData {
+        MusicData {
+            title: self.title.clone(),
+            artist: interner.intern(&self.artist),
+            album: interner.intern(&self.album),
+            duration: self.duration,
+            disc: self.disc,
+            track: self.track,
+            album_artist: self
+                .album_artist
+                .as_deref()
+                .map(|value| interner.intern(value)),
+            compilation: self.compilation,
+            embedded: self.embedded,
+            added: self.added,
+            keys: SearchKeys {
+                title: fold(&self.title),
+                artist: interner.intern(&fold(&self.artist)),
+                album: interner.intern(&fold(&self.album)),
+                album_artist: self
+                    .album_artist
+                    .as_deref()
+                    .map(|value| interner.intern(&fold(value))),
+            },
+            path,
+        }
+    }
+}
+
+pub async fn scan_music(settings: &Settings) -> Vec<Arc<MusicData>> {
+    scan_folder(
+        settings,
+        &settings.music_path,
+        &ScanProgress::default(),
+        |_| {},
+    )
+    .await
+}
+
+/// The tags of every track as last read, reused while a file's modification time is unchanged.
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(transparent)]
+pub struct Index(HashMap<PathBuf, IndexedTrack>);
+
+impl Index {
+    pub async fn load() -> Self {
+        store::load(INDEX_FILE).await
+    }
+
+    pub async fn save(&self) -> std::io::Result<()> {
+        store::save(INDEX_FILE, self).await
+    }
+
+    /// Replaces everything indexed under the update's folder with what the scan read there,
+    /// dropping the entries of files that are gone. Returns whether anything changed.
+    pub fn merge(&mut self, update: IndexUpdate) -> bool {
+        let before = self.0.len();
+        self.0.retain(|path, _| !path.starts_with(&update.folder));
+        let removed = before != self.0.len();
+        let added = !update.tracks.is_empty();
+        self.0.extend(update.tracks);
+        removed || added
+    }
+
+    /// The library as last indexed, without touching the files themselves.
+    pub fn music(&self, settings: &Settings) -> Vec<Arc<MusicData>> {
+        let mut interner = Interner::default();
+        let music = self
+            .0
+            .iter()
+            .filter(|(path, _)| path.starts_with(&settings.music_path))
+            .map(|(path, track)| track.to_music(path.clone(), &mut interner))
+            .collect();
+        into_library(music)
+    }
+}
+
+/// The tracks a folder scan read, to be merged into the [`Index`] by whoever owns it.
+pub struct IndexUpdate {
+    folder: PathBuf,
+    tracks: Vec<(PathBuf, IndexedTrack)>,
+}
+
+/// Walks `folder`, reading tags only for files that changed since they were last indexed,
+/// and stores the refreshed index for the next launch. Each track goes to `found` as soon as
+/// it is read, before the library is sorted.
+pub async fn scan_folder(
+    settings: &Settings,
+    folder: &Path,
+    progress: &ScanProgress,
+    found: impl FnMut(&MusicData),
+) -> Vec<Arc<MusicData>> {
+    let mut index = Index::load().await;
+    let (music, update) = read_folder(settings, folder, &index, progress, found).await;
+    if let Some(update) = update
+        && index.merge(update)
+        && let Err(err) = index.save().await
+    {
+        eprintln!("lrcfetch: could not save the library index: {err}");
+    }
+    music
+}
+
+/// Like [`scan_folder`], but leaves merging and saving the index to the caller, so scans of
+/// several folders at once don't each rewrite the same file.
+pub async fn read_folder(
+    settings: &Settings,
+    folder: &Path,
+    index: &Index,
+    progress: &ScanProgress,
+    mut found: impl FnMut(&MusicData),
+) -> (Vec<Arc<MusicData>>, Option<IndexUpdate>) {
+    // An unmounted or unreadable folder walks as empty; its entries stay for when it is back.
+    if tokio::fs::read_dir(folder).await.is_err() {
+        return (Vec::new(), None);
+    }
+    let mut tracks = Vec::new();
+    let music = read_music(
+        settings,
+        folder,
+        progress,
+        |path, modified, interner| {
+            let track = index
+                .0
+                .get(path)
+                .filter(|track| track.modified == modified)?;
+            Some(track.to_music(path.to_path_buf(), interner))
+        },
+        |data, modified| {
+            found(data);
+            tracks.push((data.path.clone(), IndexedTrack::new(data, modified)));
+        },
+    )
+    .await;
+    let update = IndexUpdate {
+        folder: folder.to_path_buf(),
+        tracks,
+    };
+    (music, Some(update))
+}
+
+/// The directories directly under the library root.
+pub fn top_folders(settings: &Settings) -> Vec<PathBuf> {
+    let Ok(dir) = std::fs::read_dir(&settings.music_path) else {
+        return Vec::new();
+    };
+    let ignore = Ignore::load(&settings.music_path);
+    dir.filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+        .map(|entry| entry.path())
+        .filter(|path| !ignore.is_ignored(path, true))
+        .collect()
+}
+
+/// Walks the library, reusing `previous` entries for files not modified since `since`, so
+/// only new and retagged files have their tags read again.
+pub async fn rescan_music(
+    settings: &Settings,
+    previous: &[Arc<MusicData>],
+    since: SystemTime,
+    progress: &ScanProgress,
+) -> Vec<Arc<MusicData>> {
+    let previous = previous
+        .iter()
+        .map(|data| (data.path.as_path(), data))
+        .collect::<HashMap<_, _>>();
+    read_music(
+        settings,
+        &settings.music_path,
+        progress,
+        |path, modified, _| {
+            let data = previous.get(path).filter(|_| modified < since)?;
+            Some((***data).clone())
+        },
+        |_, _| {},
+    )
+    .await
+}
+
+/// Calls `visit` with every music file under `root` and its modification time.
+fn walk_music(settings: &Settings, root: &Path, mut visit: impl FnMut(PathBuf, SystemTime)) {
+    let ignore = Ignore::load(&settings.music_path);
+    let dir = std::fs::read_dir(root);
+    let mut queue = VecDeque::new();
+    queue.push_back(dir);
+    while !queue.is_empty() {
+        let Some(Ok(dir)) = queue.pop_front() else {
+            continue;
+        };
+
+        for entry in dir {
+            let Ok(entry) = entry else {
+                continue;
+            };
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if ignore.is_ignored(&entry.path(), metadata.is_dir()) {
+                continue;
+            }
+            if metadata.is_dir() {
+                queue.push_back(std::fs::read_dir(entry.path()));
+            } else if is_music(&entry.path(), settings) {
+                let modified = metadata.modified().unwrap_or_else(|_| SystemTime::now());
+                visit(entry.path(), modified);
+            }
+        }
+    }
+}
+
+/// Walks `root`, taking entries from `reuse` where it has one and reading tags for the rest,
+/// and hands each track to `found` as soon as it is known. Tags are read on blocking workers,
+/// up to `concurrent_local_reads` files at once.
+async fn read_music(
+    settings: &Settings,
+    root: &Path,
+    progress: &ScanProgress,
+    mut reuse: impl FnMut(&Path, SystemTime, &mut Interner) -> Option<MusicData>,
+    mut found: impl FnMut(&MusicData, SystemTime),
+) -> Vec<Arc<MusicData>> {
+    let (paths, mut walked) = tokio::sync::mpsc::unbounded_channel();
+    let walk_settings = settings.clone();
+    let walk_root = root.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        walk_music(&walk_settings, &walk_root, |path, modified| {
+            let _ = paths.send((path, modified));
+        })
+    });
+    let limiter = Arc::new(Semaphore::new(settings.concurrent_local_reads.max(1)));
+    let mut reads = JoinSet::new();
+    let mut walking = true;
+    let mut res = Vec::new();
+    let mut interner = Interner::default();
+    loop {
+        tokio::select! {
+            file = walked.recv(), if walking => {
+                let Some((path, modified)) = file else {
+                    walking = false;
+                    continue;
+                };
+                progress.discovered.fetch_add(1, Ordering::Relaxed);
+                if let Some(data) = reuse(&path, modified, &mut interner) {
+                    progress.loaded.fetch_add(1, Ordering::Relaxed);
+                    found(&data, modified);
+                    res.push(data);
+                    continue;
+                }
+                let permit = limiter.clone().acquire_owned().await;
+                reads.spawn_blocking(move || {
+                    let _permit = permit;
+                    (MusicData::from_file(path, &mut Interner::default()), modified)
+                });
+            }
+            Some(read) = reads.join_next() => {
+                progress.loaded.fetch_add(1, Ordering::Relaxed);
+                if let Ok((Ok(mut data), modified)) = read {
+                    interner.share(&mut data);
+                    found(&data, modified);
+                    res.push(data);
+                }
+            }
+            else => break,
+        }
+    }
+    into_library(res)
+}
+
+fn into_library(mut music: Vec<MusicData>) -> Vec<Arc<MusicData>> {
+    detect_compilations(&mut music);
+    music.sort_by(|a, b| a.album_order().cmp(&b.album_order()));
+    music.into_iter().map(Arc::new).collect()
+}
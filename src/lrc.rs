@@ -0,0 +1,276 @@
+use std::time::Duration;
+
+use crate::settings::TimestampPrecision;
+
+pub fn parse_timestamp(tag: &str) -> Option<Duration> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    let minutes = minutes.trim().parse::<u64>().ok()?;
+    let (seconds, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+    let seconds = seconds.trim().parse::<u64>().ok()?;
+    if seconds >= 60 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let millis = match fraction.len() {
+        0 => 0,
+        1..=3 => fraction.parse::<u64>().ok()? * 10u64.pow(3 - fraction.len() as u32),
+        _ => fraction[..3].parse::<u64>().ok()?,
+    };
+    Some(Duration::from_millis(
+        (minutes * 60 + seconds) * 1000 + millis,
+    ))
+}
+
+pub fn format_timestamp(timestamp: Duration, precision: TimestampPrecision) -> String {
+    let millis = timestamp.as_millis();
+    let (minutes, seconds) = (millis / 60_000, millis / 1000 % 60);
+    match precision {
+        TimestampPrecision::Milliseconds => {
+            format!("{minutes:02}:{seconds:02}.{:03}", millis % 1000)
+        }
+        TimestampPrecision::Keep | TimestampPrecision::Centiseconds => {
+            format!("{minutes:02}:{seconds:02}.{:02}", millis % 1000 / 10)
+        }
+    }
+}
+
+/// Rewrites every timestamp tag to `precision`, leaving metadata tags and text alone.
+pub fn normalize(text: &str, precision: TimestampPrecision) -> String {
+    if precision == TimestampPrecision::Keep {
+        return text.to_string();
+    }
+    let mut normalized = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        // Keep each line's own ending, so CRLF files are not rewritten just for their newlines.
+        let ending = if line.ends_with("\r\n") {
+            "\r\n"
+        } else if line.ends_with('\n') {
+            "\n"
+        } else {
+            ""
+        };
+        let line = &line[..line.len() - ending.len()];
+        let (tags, rest) = leading_tags(line);
+        if tags.iter().any(|tag| parse_timestamp(tag).is_some()) {
+            for tag in tags {
+                match parse_timestamp(tag) {
+                    Some(timestamp) => normalized
+                        .push_str(&format!("[{}]", format_timestamp(timestamp, precision))),
+                    None => normalized.push_str(&format!("[{tag}]")),
+                }
+            }
+            normalized.push_str(rest);
+        } else {
+            normalized.push_str(line);
+        }
+        normalized.push_str(ending);
+    }
+    normalized
+}
+
+fn is_metadata_tag(tag: &str) -> bool {
+    tag.split_once(':')
+        .is_some_and(|(key, _)| !key.is_empty() && key.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+/// Splits the bracketed tags at the start of a line from its text, so
+/// `[01:02.3][02:30.1]Line` yields both timestamps.
+pub fn leading_tags(line: &str) -> (Vec<&str>, &str) {
+    let mut tags = Vec::new();
+    let mut rest = line.trim();
+    while let Some((tag, after)) = rest.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+        tags.push(tag);
+        rest = after;
+    }
+    (tags, rest)
+}
+
+#[derive(Default, Debug)]
+pub struct LrcReport {
+    pub timed_lines: usize,
+    pub invalid_lines: Vec<usize>,
+    pub last_timestamp: Option<Duration>,
+}
+
+pub fn validate(text: &str) -> LrcReport {
+    let mut report = LrcReport::default();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (tags, _) = leading_tags(line);
+        if tags.is_empty() {
+            report.invalid_lines.push(number + 1);
+            continue;
+        }
+        let mut valid = true;
+        for tag in tags {
+            if let Some(timestamp) = parse_timestamp(tag) {
+                report.timed_lines += 1;
+                report.last_timestamp = report.last_timestamp.max(Some(timestamp));
+            } else if !is_metadata_tag(tag) {
+                valid = false;
+            }
+        }
+        if !valid {
+            report.invalid_lines.push(number + 1);
+        }
+    }
+    report
+}
+
+/// Splits LRC text into timestamped lines, expanding lines that carry several timestamps.
+pub fn timed_lines(text: &str) -> Vec<(Duration, &str)> {
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let (tags, rest) = leading_tags(line);
+        let rest = rest.trim();
+        lines.extend(
+            tags.into_iter()
+                .filter_map(parse_timestamp)
+                .map(|timestamp| (timestamp, rest)),
+        );
+    }
+    lines.sort_by_key(|&(timestamp, _)| timestamp);
+    lines
+}
+
+/// Adds each translated line right after the original line with the same timestamp.
+pub fn merge_translation(lrc: &str, translation: &str) -> String {
+    let mut translated = std::collections::HashMap::new();
+    for (timestamp, line) in timed_lines(translation) {
+        if !line.is_empty() {
+            translated.insert(timestamp, line);
+        }
+    }
+    let mut merged = String::with_capacity(lrc.len() + translation.len());
+    for line in lrc.lines() {
+        merged.push_str(line);
+        merged.push('\n');
+        let (tags, _) = leading_tags(line);
+        let translation = tags
+            .iter()
+            .filter_map(|tag| parse_timestamp(tag))
+            .find_map(|timestamp| translated.get(&timestamp));
+        if let Some(translation) = translation {
+            // Repeat every timestamp so the translation follows each occurrence.
+            for tag in tags {
+                merged.push_str(&format!("[{tag}]"));
+            }
+            merged.push_str(translation);
+            merged.push('\n');
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn timestamp_fractions() {
+        assert_eq!(parse_timestamp("01:02"), Some(ms(62_000)));
+        assert_eq!(parse_timestamp("01:02.3"), Some(ms(62_300)));
+        assert_eq!(parse_timestamp("01:02.34"), Some(ms(62_340)));
+        assert_eq!(parse_timestamp("01:02.345"), Some(ms(62_345)));
+        assert_eq!(parse_timestamp("01:02.3456"), Some(ms(62_345)));
+        assert_eq!(parse_timestamp("123:00.00"), Some(ms(7_380_000)));
+        assert_eq!(parse_timestamp(" 1: 2.5"), Some(ms(62_500)));
+    }
+
+    #[test]
+    fn timestamp_rejects() {
+        assert_eq!(parse_timestamp("01:60.00"), None);
+        assert_eq!(parse_timestamp("01:02.3a"), None);
+        assert_eq!(parse_timestamp("-1:02.00"), None);
+        assert_eq!(parse_timestamp("0102"), None);
+        assert_eq!(parse_timestamp("ar:Someone"), None);
+        assert_eq!(parse_timestamp(""), None);
+    }
+
+    #[test]
+    fn leading_tags_split() {
+        assert_eq!(
+            leading_tags("[01:02.3][02:30.1]Line"),
+            (vec!["01:02.3", "02:30.1"], "Line")
+        );
+        assert_eq!(leading_tags("  [ti:Song]  "), (vec!["ti:Song"], ""));
+        assert_eq!(leading_tags("No tags [00:01]"), (vec![], "No tags [00:01]"));
+        assert_eq!(leading_tags("[unclosed"), (vec![], "[unclosed"));
+    }
+
+    #[test]
+    fn validate_counts_and_flags() {
+        let text = "[ar:Someone]\n[length:03:20]\n\n[00:01.00][01:00.00]Chorus\nplain line\n[oops]Bad\n[00:30.00]Verse";
+        let report = validate(text);
+        assert_eq!(report.timed_lines, 3);
+        assert_eq!(report.invalid_lines, vec![5, 6]);
+        assert_eq!(report.last_timestamp, Some(ms(60_000)));
+    }
+
+    #[test]
+    fn validate_plain_text() {
+        let report = validate("just words\nmore words");
+        assert_eq!(report.timed_lines, 0);
+        assert_eq!(report.invalid_lines, vec![1, 2]);
+        assert_eq!(report.last_timestamp, None);
+    }
+
+    #[test]
+    fn timed_lines_expand_and_sort() {
+        let text = "[ar:Someone]\n[00:10.00][00:30.00] Chorus \n[00:20.00]Verse\n[00:40.00]";
+        assert_eq!(
+            timed_lines(text),
+            vec![
+                (ms(10_000), "Chorus"),
+                (ms(20_000), "Verse"),
+                (ms(30_000), "Chorus"),
+                (ms(40_000), ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn normalize_precisions() {
+        let text = "[ti:Song]\n[00:01.5][00:02.345]Both\n[00:03]Whole\nplain\n";
+        assert_eq!(
+            normalize(text, TimestampPrecision::Centiseconds),
+            "[ti:Song]\n[00:01.50][00:02.34]Both\n[00:03.00]Whole\nplain\n"
+        );
+        assert_eq!(
+            normalize(text, TimestampPrecision::Milliseconds),
+            "[ti:Song]\n[00:01.500][00:02.345]Both\n[00:03.000]Whole\nplain\n"
+        );
+        assert_eq!(normalize(text, TimestampPrecision::Keep), text);
+    }
+
+    #[test]
+    fn normalize_keeps_crlf_line_endings() {
+        let text = "[ar:Someone]\r\n[00:01.5]One\r\n[00:02.25]Two";
+        assert_eq!(
+            normalize(text, TimestampPrecision::Centiseconds),
+            "[ar:Someone]\r\n[00:01.50]One\r\n[00:02.25]Two"
+        );
+        let normalized = "[00:01.50]One\r\n[00:02.25]Two\r\n";
+        assert_eq!(
+            normalize(normalized, TimestampPrecision::Centiseconds),
+            normalized
+        );
+    }
+
+    #[test]
+    fn merge_translation_follows_each_timestamp() {
+        let lrc = "[ar:Someone]\n[00:01.00][00:05.00]Hola\n[00:02.00]Adios\n[00:03.00]Sin traducir";
+        let translation = "[00:01.00]Hello\n[00:02.00]Goodbye\n[00:03.00]";
+        assert_eq!(
+            merge_translation(lrc, translation),
+            "[ar:Someone]\n[00:01.00][00:05.00]Hola\n[00:01.00][00:05.00]Hello\n\
+             [00:02.00]Adios\n[00:02.00]Goodbye\n[00:03.00]Sin traducir\n"
+        );
+    }
+}
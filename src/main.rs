@@ -1,27 +1,68 @@
 use std::{
-    collections::{HashMap, VecDeque},
-    env::{self, current_dir, home_dir},
-    path::{PathBuf, absolute},
-    sync::Arc,
-    time::Duration,
-    usize,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{Arc, atomic::Ordering},
+    time::{Duration, Instant, SystemTime},
 };
 
+use clap::Parser;
+use lru::LruCache;
 use serde::{Deserialize, Serialize};
-use tokio::{io::AsyncWriteExt, sync::Semaphore, task::JoinSet};
+use tokio::{
+    sync::{Semaphore, watch},
+    task::JoinSet,
+};
+mod cache;
+mod changelog;
+mod cli;
+mod filter;
+mod ignore;
+mod library;
+mod lrc;
 mod musicdata;
+mod preset;
+mod providers;
+mod review;
+mod settings;
+mod store;
+mod throttle;
+mod variants;
 
 use crossterm::event::{self, Event, KeyCode};
 use ratatui::{
     buffer::Buffer,
-    layout::{Alignment, Layout, Rect},
-    style::{Color, Style},
-    text::Text,
-    widgets::{self, Block, Clear, List, ListState, StatefulWidget, Table, TableState, Widget},
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::Style,
+    text::{Line, Span, Text},
+    widgets::{
+        self, Block, Cell, Clear, List, ListState, Paragraph, Row, StatefulWidget, Table,
+        TableState, Widget, Wrap,
+    },
 };
 
-use crate::musicdata::{Lyrics, MusicData};
-const KEYMAP: [(KeyBind, Func); 10] = [
+use crate::cache::{MISSES_FILE, Misses, ResponseCache};
+use crate::changelog::release_notes;
+use crate::cli::Cli;
+use crate::filter::Filter;
+use crate::library::{
+    DURATIONS_FILE, FLAGS_FILE, Index, IndexUpdate, LibraryEntry, ScanProgress, TrackFlag,
+    apply_duration_overrides, check_lyrics_counted, decode_duration, read_folder, rescan_music,
+    to_m3u, top_folders,
+};
+use crate::musicdata::{
+    Candidate, Interner, LRCLIB_URL, Lyrics, LyricsStatus, MusicData, QueryError, Scored, fold,
+};
+use crate::preset::{PRESET_FILE, Preset};
+use crate::providers::Registry;
+use crate::review::{REVIEW_FILE, Review};
+use crate::settings::{
+    LayoutMode, LyricsOutput, Settings, StatusIcons, Theme, build_client, get_or_create_config,
+    user_agent,
+};
+use crate::throttle::{Politeness, Throttle};
+const KEYMAP: [(KeyBind, Func); 88] = [
     (
         KeyBind {
             keycode: KeyCode::Char('a'),
@@ -92,344 +133,2455 @@ const KEYMAP: [(KeyBind, Func); 10] = [
         },
         Func::ScanSelected,
     ),
-];
-
-const HIGHLIGHT_STYLE: Style = Style::new().bg(Color::White).fg(Color::Black);
-const MUSIC_EXTENSIONS: [&str; 1] = ["flac"];
-
-#[derive(Default)]
-struct Filter {
-    title: Option<String>,
-    artist: Option<String>,
-    album: Option<String>,
-}
-impl Filter {
-    fn apply(&self, item: &MusicData) -> bool {
-        if let Some(album_filter) = &self.album {
-            if !item
-                .album
-                .to_ascii_lowercase()
-                .contains(album_filter.to_ascii_lowercase().as_str())
-            {
-                return false;
-            }
-        }
-        if let Some(artist_filter) = &self.artist {
-            if !item
-                .artist
-                .to_ascii_lowercase()
-                .contains(artist_filter.to_ascii_lowercase().as_str())
-            {
-                return false;
-            }
-        }
-        if let Some(title_filter) = &self.title {
-            if !item
-                .title
-                .to_ascii_lowercase()
-                .contains(title_filter.to_ascii_lowercase().as_str())
-            {
-                return false;
+    (
+        KeyBind {
+            keycode: KeyCode::Char('v'),
+            screen: Screens::Main,
+        },
+        Func::ToggleLayout,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('l'),
+            screen: Screens::Main,
+        },
+        Func::OpenLyricsPopup,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('l'),
+            screen: Screens::Lyrics,
+        },
+        Func::CloseLyricsPopup,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('q'),
+            screen: Screens::Lyrics,
+        },
+        Func::CloseLyricsPopup,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Esc,
+            screen: Screens::Lyrics,
+        },
+        Func::CloseLyricsPopup,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('J'),
+            screen: Screens::Main,
+        },
+        Func::ScrollLyricsDown,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('K'),
+            screen: Screens::Main,
+        },
+        Func::ScrollLyricsUp,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('j'),
+            screen: Screens::Lyrics,
+        },
+        Func::ScrollLyricsDown,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('k'),
+            screen: Screens::Lyrics,
+        },
+        Func::ScrollLyricsUp,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::F(12),
+            screen: Screens::Main,
+        },
+        Func::ToggleDebugOverlay,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('e'),
+            screen: Screens::Main,
+        },
+        Func::OpenFailuresPopup,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('R'),
+            screen: Screens::Main,
+        },
+        Func::RetryFailed,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('R'),
+            screen: Screens::Failures,
+        },
+        Func::RetryFailed,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('q'),
+            screen: Screens::Failures,
+        },
+        Func::CloseFailuresPopup,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Esc,
+            screen: Screens::Failures,
+        },
+        Func::CloseFailuresPopup,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('j'),
+            screen: Screens::Failures,
+        },
+        Func::FailuresSelectNext,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('k'),
+            screen: Screens::Failures,
+        },
+        Func::FailuresSelectPrevious,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('r'),
+            screen: Screens::Main,
+        },
+        Func::ToggleLimiterPanel,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char(' '),
+            screen: Screens::Main,
+        },
+        Func::ToggleMark,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('Q'),
+            screen: Screens::Main,
+        },
+        Func::ToggleMacroRecording,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('@'),
+            screen: Screens::Main,
+        },
+        Func::ReplayMacro,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('c'),
+            screen: Screens::Main,
+        },
+        Func::OpenCandidates,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('q'),
+            screen: Screens::Candidates,
+        },
+        Func::CloseCandidates,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Esc,
+            screen: Screens::Candidates,
+        },
+        Func::CloseCandidates,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('j'),
+            screen: Screens::Candidates,
+        },
+        Func::CandidatesSelectNext,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('k'),
+            screen: Screens::Candidates,
+        },
+        Func::CandidatesSelectPrevious,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Enter,
+            screen: Screens::Candidates,
+        },
+        Func::AcceptCandidate,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('x'),
+            screen: Screens::Candidates,
+        },
+        Func::RejectCandidates,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('i'),
+            screen: Screens::Main,
+        },
+        Func::OpenDetails,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('q'),
+            screen: Screens::Details,
+        },
+        Func::CloseDetails,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Esc,
+            screen: Screens::Details,
+        },
+        Func::CloseDetails,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('d'),
+            screen: Screens::Details,
+        },
+        Func::EditDuration,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('D'),
+            screen: Screens::Details,
+        },
+        Func::RecomputeDuration,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('y'),
+            screen: Screens::AlbumApply,
+        },
+        Func::ConfirmAlbumApply,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('n'),
+            screen: Screens::AlbumApply,
+        },
+        Func::CancelAlbumApply,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('q'),
+            screen: Screens::AlbumApply,
+        },
+        Func::CancelAlbumApply,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Esc,
+            screen: Screens::AlbumApply,
+        },
+        Func::CancelAlbumApply,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('E'),
+            screen: Screens::Main,
+        },
+        Func::CycleLyricsOutput,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('x'),
+            screen: Screens::Main,
+        },
+        Func::CancelQueued,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Esc,
+            screen: Screens::Main,
+        },
+        Func::CancelFetches,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('p'),
+            screen: Screens::Main,
+        },
+        Func::TogglePause,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('X'),
+            screen: Screens::Main,
+        },
+        Func::ExtractEmbedded,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('P'),
+            screen: Screens::Main,
+        },
+        Func::ExportMissingPlaylist,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('T'),
+            screen: Screens::Main,
+        },
+        Func::TrashAndRefetch,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('g'),
+            screen: Screens::Main,
+        },
+        Func::CycleFlag,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('V'),
+            screen: Screens::Main,
+        },
+        Func::PasteLyrics,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('w'),
+            screen: Screens::Main,
+        },
+        Func::SaveSelected,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('W'),
+            screen: Screens::Main,
+        },
+        Func::SaveAll,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('U'),
+            screen: Screens::Main,
+        },
+        Func::ExportUnsaved,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('h'),
+            screen: Screens::Main,
+        },
+        Func::OpenActivity,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('q'),
+            screen: Screens::Activity,
+        },
+        Func::CloseActivity,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Esc,
+            screen: Screens::Activity,
+        },
+        Func::CloseActivity,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('j'),
+            screen: Screens::Activity,
+        },
+        Func::ActivitySelectNext,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('k'),
+            screen: Screens::Activity,
+        },
+        Func::ActivitySelectPrevious,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Enter,
+            screen: Screens::Activity,
+        },
+        Func::JumpToActivity,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('q'),
+            screen: Screens::WhatsNew,
+        },
+        Func::CloseWhatsNew,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Esc,
+            screen: Screens::WhatsNew,
+        },
+        Func::CloseWhatsNew,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('d'),
+            screen: Screens::WhatsNew,
+        },
+        Func::DismissWhatsNew,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('j'),
+            screen: Screens::WhatsNew,
+        },
+        Func::WhatsNewScrollDown,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('k'),
+            screen: Screens::WhatsNew,
+        },
+        Func::WhatsNewScrollUp,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('?'),
+            screen: Screens::Main,
+        },
+        Func::OpenWhatsNew,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('u'),
+            screen: Screens::Main,
+        },
+        Func::ResumeQueue,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('n'),
+            screen: Screens::Main,
+        },
+        Func::ReviewNext,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('o'),
+            screen: Screens::Main,
+        },
+        Func::ToggleSortAdded,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::F(5),
+            screen: Screens::Main,
+        },
+        Func::Rescan,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('F'),
+            screen: Screens::Main,
+        },
+        Func::OpenFolders,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('q'),
+            screen: Screens::Folders,
+        },
+        Func::CloseFolders,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Esc,
+            screen: Screens::Folders,
+        },
+        Func::CloseFolders,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('j'),
+            screen: Screens::Folders,
+        },
+        Func::FoldersSelectNext,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('k'),
+            screen: Screens::Folders,
+        },
+        Func::FoldersSelectPrevious,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Enter,
+            screen: Screens::Folders,
+        },
+        Func::ShowFolder,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('s'),
+            screen: Screens::Folders,
+        },
+        Func::ScanFolder,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('b'),
+            screen: Screens::Main,
+        },
+        Func::OpenQueue,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('q'),
+            screen: Screens::Queue,
+        },
+        Func::CloseQueue,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Esc,
+            screen: Screens::Queue,
+        },
+        Func::CloseQueue,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('j'),
+            screen: Screens::Queue,
+        },
+        Func::QueueSelectNext,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('k'),
+            screen: Screens::Queue,
+        },
+        Func::QueueSelectPrevious,
+    ),
+    (
+        KeyBind {
+            keycode: KeyCode::Char('x'),
+            screen: Screens::Queue,
+        },
+        Func::RemoveFromQueue,
+    ),
+];
+
+const RELEASES_FILE: &str = "releases.ron";
+const MISSING_PLAYLIST: &str = "missing-lyrics.m3u";
+/// Unsaved lyrics in the `export` format, so `import` can write them later.
+const UNSAVED_EXPORT: &str = "unsaved-lyrics.json";
+const SEEN_VERSION_FILE: &str = "seen_version.ron";
+const VARIANTS_FILE: &str = "variants.ron";
+const QUEUE_FILE: &str = "queue.ron";
+const QUEUE_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+const PREVIEW_CACHE_SIZE: NonZeroUsize = NonZeroUsize::new(32).unwrap();
+const PREFETCH_RADIUS: usize = 3;
+/// How far back fetch completions count toward the rate and ETA in the gauge.
+const RATE_WINDOW: Duration = Duration::from_secs(30);
+const SEARCH_CACHE_SIZE: NonZeroUsize = NonZeroUsize::new(64).unwrap();
+const ACTIVITY_LIMIT: usize = 500;
+const MIN_WIDTH: u16 = 40;
+const MIN_HEIGHT: u16 = 10;
+const STACKED_WIDTH: u16 = 80;
+const ANNOUNCEMENT_LINES: usize = 3;
+
+#[derive(Clone, Default)]
+struct Screen<'a> {
+    tracks: Table<'a>,
+}
+
+impl Screen<'_> {
+    fn render_lyrics(&self, area: Rect, buf: &mut Buffer, state: &mut State) {
+        let source = state
+            .selected_music()
+            .and_then(|item| state.sources.get(&item.path));
+        let unsaved = state
+            .selected_music()
+            .is_some_and(|item| state.unsaved.contains_key(&item.path));
+        let block = match (source, unsaved) {
+            (Some(source), true) => panel(state).title(format!("Lyrics (from {source}, unsaved)")),
+            (None, true) => panel(state).title("Lyrics (unsaved)"),
+            (Some(source), false) => panel(state).title(format!("Lyrics (from {source})")),
+            (None, false) => panel(state).title("Lyrics"),
+        };
+        'lyrics: {
+            let Some(item) = state.selected_music() else {
+                break 'lyrics;
+            };
+            let scroll = state.lyrics_scroll.get(&item.path).copied().unwrap_or(0);
+            let txt = match (state.lyrics.get(&item.path), state.outcomes.get(&item.path)) {
+                (None | Some(LyricsStatus::None), Some(FetchOutcome::Error(reason))) => {
+                    Text::raw(format!("Fetch failed: {reason}"))
+                }
+                (None | Some(LyricsStatus::None), Some(FetchOutcome::NotFound)) => {
+                    Text::raw("No provider has lyrics for this track")
+                }
+                (None | Some(LyricsStatus::None), Some(FetchOutcome::NeedsReview)) => {
+                    Text::raw("Only borderline matches were found; n reviews them")
+                }
+                (None, _) => Text::raw("Not found"),
+                (Some(LyricsStatus::None), _) => Text::raw("None"),
+                (Some(LyricsStatus::Instrumental), _) => Text::raw("Instrumental"),
+                (Some(LyricsStatus::Synced | LyricsStatus::Plain), _) => {
+                    match state.preview_cache.peek(&item.path) {
+                        Some(Lyrics::Plain(txt) | Lyrics::Synced(txt)) => Text::raw(txt.as_str()),
+                        _ => match state.preview_failed.get(&item.path) {
+                            Some(err) => Text::raw(format!("Could not read the lyrics: {err}")),
+                            None => Text::raw("Loading..."),
+                        },
+                    }
+                }
+            };
+            Paragraph::new(txt)
+                .scroll((scroll, 0))
+                .render(block.inner(area), buf);
+        }
+        block.render(area, buf);
+    }
+    fn render_lyrics_popup(&self, area: Rect, buf: &mut Buffer, state: &mut State) {
+        use ratatui::layout::Constraint::Percentage;
+        use ratatui::layout::Flex::Center;
+
+        let [area] = Layout::vertical([Percentage(80)]).flex(Center).areas(area);
+        let [area] = Layout::horizontal([Percentage(80)])
+            .flex(Center)
+            .areas(area);
+        Clear.render(area, buf);
+        self.render_lyrics(area, buf, state);
+    }
+    fn render_failures_popup(&self, area: Rect, buf: &mut Buffer, state: &mut State) {
+        use ratatui::layout::Constraint::Percentage;
+        use ratatui::layout::Flex::Center;
+
+        let [area] = Layout::vertical([Percentage(60)]).flex(Center).areas(area);
+        let [area] = Layout::horizontal([Percentage(70)])
+            .flex(Center)
+            .areas(area);
+        Clear.render(area, buf);
+        let border = panel(state)
+            .title(format!(
+                "Failures ({}) - R retries all",
+                state.failures.len()
+            ))
+            .title_alignment(Alignment::Center);
+        let inner = border.inner(area);
+        border.render(area, buf);
+        let list = List::new(state.failures.iter().map(|failure| {
+            format!(
+                "{} - {}: {}",
+                failure.data.artist, failure.data.title, failure.reason
+            )
+        }))
+        .highlight_style(state.settings.theme.highlight());
+        StatefulWidget::render(list, inner, buf, &mut state.failures_state);
+    }
+    fn render_activity_popup(&self, area: Rect, buf: &mut Buffer, state: &mut State) {
+        use ratatui::layout::Constraint::Percentage;
+        use ratatui::layout::Flex::Center;
+
+        let [area] = Layout::vertical([Percentage(60)]).flex(Center).areas(area);
+        let [area] = Layout::horizontal([Percentage(70)])
+            .flex(Center)
+            .areas(area);
+        Clear.render(area, buf);
+        let border = panel(state)
+            .title("Activity - enter jumps to the track")
+            .title_alignment(Alignment::Center);
+        let inner = border.inner(area);
+        border.render(area, buf);
+        let list =
+            List::new(state.activity.iter().map(|activity| {
+                format!("{:>8}  {}", ago(activity.at.elapsed()), activity.message)
+            }))
+            .highlight_style(state.settings.theme.highlight());
+        StatefulWidget::render(list, inner, buf, &mut state.activity_state);
+    }
+    fn render_folders_popup(&self, area: Rect, buf: &mut Buffer, state: &mut State) {
+        use ratatui::layout::Constraint::Percentage;
+        use ratatui::layout::Flex::Center;
+
+        let [area] = Layout::vertical([Percentage(60)]).flex(Center).areas(area);
+        let [area] = Layout::horizontal([Percentage(70)])
+            .flex(Center)
+            .areas(area);
+        Clear.render(area, buf);
+        let border = panel(state)
+            .title("Folders - enter shows, s fetches missing")
+            .title_alignment(Alignment::Center);
+        let inner = border.inner(area);
+        border.render(area, buf);
+        let list = List::new(state.folders.iter().map(|folder| {
+            let name = folder.path.file_name().map_or_else(
+                || folder.path.display().to_string(),
+                |name| name.to_string_lossy().into_owned(),
+            );
+            format!(
+                "{}{name}  ({} tracks, {} unsynced){}",
+                "  ".repeat(folder.depth),
+                folder.tracks,
+                folder.unsynced,
+                if folder.loaded { "" } else { " - not loaded" }
+            )
+        }))
+        .highlight_style(state.settings.theme.highlight());
+        StatefulWidget::render(list, inner, buf, &mut state.folders_state);
+    }
+    fn render_queue_popup(&self, area: Rect, buf: &mut Buffer, state: &mut State) {
+        use ratatui::layout::Constraint::Percentage;
+        use ratatui::layout::Flex::Center;
+
+        let [area] = Layout::vertical([Percentage(60)]).flex(Center).areas(area);
+        let [area] = Layout::horizontal([Percentage(70)])
+            .flex(Center)
+            .areas(area);
+        Clear.render(area, buf);
+        let border = panel(state)
+            .title(format!(
+                "Queue - {} running, {} waiting - x removes",
+                state.fetch_tasks.len(),
+                state.fetch_queue.len()
+            ))
+            .title_alignment(Alignment::Center);
+        let inner = border.inner(area);
+        border.render(area, buf);
+        let entry = |status: String, data: &MusicData| {
+            format!(
+                "{status:>8}  {} - {}  ({})",
+                data.artist,
+                data.title,
+                state.settings.providers_for(data).join(" > ")
+            )
+        };
+        // Only the rows in view get a line, since the queue can hold the whole library.
+        let running = state.running();
+        let total = running.len() + state.fetch_queue.len();
+        let height = inner.height as usize;
+        let selected = state
+            .queue_state
+            .selected()
+            .map(|selected| selected.min(total.saturating_sub(1)));
+        let mut offset = state.queue_state.offset().min(total.saturating_sub(height));
+        if let Some(selected) = selected {
+            offset = offset
+                .min(selected)
+                .max((selected + 1).saturating_sub(height));
+        }
+        let rows = running
+            .iter()
+            .map(|(_, fetch)| (Some(fetch.started), &*fetch.data))
+            .chain(state.fetch_queue.iter().map(|data| (None, &**data)))
+            .skip(offset)
+            .take(height)
+            .map(|(started, data)| match started {
+                Some(started) => entry(format!("{}s", started.elapsed().as_secs()), data),
+                None => entry("queued".to_string(), data),
+            })
+            .collect::<Vec<_>>();
+        let list = List::new(rows).highlight_style(state.settings.theme.highlight());
+        let mut window =
+            ListState::default().with_selected(selected.map(|selected| selected - offset));
+        StatefulWidget::render(list, inner, buf, &mut window);
+        state.queue_state.select(selected);
+        *state.queue_state.offset_mut() = offset;
+    }
+    fn render_album_apply_popup(&self, area: Rect, buf: &mut Buffer, state: &mut State) {
+        use ratatui::layout::Constraint::{Length, Percentage};
+        use ratatui::layout::Flex::Center;
+
+        let Some(apply) = &state.album_apply else {
+            return;
+        };
+        let [area] = Layout::vertical([Length(4)]).flex(Center).areas(area);
+        let [area] = Layout::horizontal([Percentage(60)])
+            .flex(Center)
+            .areas(area);
+        Clear.render(area, buf);
+        let block = panel(state)
+            .title("Apply release")
+            .title_alignment(Alignment::Center);
+        let txt = Paragraph::new(format!(
+            "Fetch the other {} unmatched tracks of this album as \"{}\"?\ny - apply, n - skip",
+            apply.tracks.len(),
+            apply.album
+        ))
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+        txt.render(block.inner(area), buf);
+        block.render(area, buf);
+    }
+    fn render_whats_new_popup(&self, area: Rect, buf: &mut Buffer, state: &mut State) {
+        use ratatui::layout::Constraint::Percentage;
+        use ratatui::layout::Flex::Center;
+
+        let version = env!("CARGO_PKG_VERSION");
+        let Some(notes) = release_notes(version) else {
+            return;
+        };
+        let [area] = Layout::vertical([Percentage(70)]).flex(Center).areas(area);
+        let [area] = Layout::horizontal([Percentage(70)])
+            .flex(Center)
+            .areas(area);
+        Clear.render(area, buf);
+        let block = panel(state)
+            .title(format!("What's new in {version}"))
+            .title_bottom("j/k - scroll, q - close, d - don't show again")
+            .title_alignment(Alignment::Center);
+        let txt = Paragraph::new(notes)
+            .wrap(Wrap { trim: false })
+            .scroll((state.whats_new_scroll, 0));
+        txt.render(block.inner(area), buf);
+        block.render(area, buf);
+    }
+    fn render_details_popup(&self, area: Rect, buf: &mut Buffer, state: &mut State) {
+        use ratatui::layout::Constraint::{Length, Percentage};
+        use ratatui::layout::Flex::Center;
+
+        let Some(data) = state.selected_music().cloned() else {
+            return;
+        };
+        let number = |value: Option<u32>| value.map_or("-".to_string(), |value| value.to_string());
+        let duration = if state.duration_overrides.contains_key(&data.path) {
+            format!("{}s (override)", data.duration)
+        } else if data.duration == 0 {
+            "unknown (audio stream unreadable, not fetched)".to_string()
+        } else {
+            format!("{}s (from tags)", data.duration)
+        };
+        let status = state
+            .lyrics
+            .get(&data.path)
+            .map_or("unknown".to_string(), |status| status.to_string());
+        let last_fetch = match state.outcomes.get(&data.path) {
+            None => "-".to_string(),
+            Some(FetchOutcome::NotFound) => "not found".to_string(),
+            Some(FetchOutcome::NeedsReview) => "needs review".to_string(),
+            Some(FetchOutcome::Error(reason)) => format!("failed: {reason}"),
+            Some(FetchOutcome::Found(status)) => format!("found {status}"),
+        };
+        let mut lines = vec![
+            format!("Path:         {}", data.path.display()),
+            format!("Title:        {}", data.title),
+            format!("Artist:       {}", data.artist),
+            format!("Album:        {}", data.album),
+            format!("Album artist: {}", data.group_artist()),
+            format!("Disc/track:   {}/{}", number(data.disc), number(data.track)),
+            format!("Duration:     {duration}"),
+            format!("Lyrics:       {status}"),
+            format!(
+                "Source:       {}",
+                state.sources.get(&data.path).copied().unwrap_or("-")
+            ),
+            format!("Last fetch:   {last_fetch}"),
+            format!(
+                "Flag:         {}",
+                state
+                    .flags
+                    .get(&data.path)
+                    .map_or("-".to_string(), |flag| flag.to_string())
+            ),
+            String::new(),
+            "d - edit duration, D - recompute by decoding".to_string(),
+        ];
+        if let Some(status) = &state.details_status {
+            lines.push(status.clone());
+        }
+        let [area] = Layout::vertical([Length(lines.len() as u16 + 2)])
+            .flex(Center)
+            .areas(area);
+        let [area] = Layout::horizontal([Percentage(70)])
+            .flex(Center)
+            .areas(area);
+        Clear.render(area, buf);
+        let block = panel(state)
+            .title("Details")
+            .title_alignment(Alignment::Center);
+        let txt = Paragraph::new(lines.join("\n"));
+        txt.render(block.inner(area), buf);
+        block.render(area, buf);
+    }
+    fn render_candidates_popup(&self, area: Rect, buf: &mut Buffer, state: &mut State) {
+        use ratatui::layout::Constraint::{Fill, Length, Percentage};
+        use ratatui::layout::Flex::Center;
+
+        let [area] = Layout::vertical([Percentage(60)]).flex(Center).areas(area);
+        let [area] = Layout::horizontal([Percentage(95)])
+            .flex(Center)
+            .areas(area);
+        Clear.render(area, buf);
+        let Some(data) = state.candidates_for.clone() else {
+            return;
+        };
+        let mut title = format!("Candidates for {} - {}", data.artist, data.title);
+        if let Some(status) = &state.candidates_status {
+            title.push_str(&format!(" ({status})"));
+        }
+        let border = panel(state).title(title).title_alignment(Alignment::Center);
+        let inner = border.inner(area);
+        border.render(area, buf);
+        let [inner, preview_area] = Layout::horizontal([Fill(3), Fill(2)]).areas(inner);
+        let preview = match state
+            .candidates_state
+            .selected()
+            .and_then(|selected| state.candidates.get(selected))
+            .map(Candidate::lyrics)
+        {
+            Some(Lyrics::Synced(text) | Lyrics::Plain(text)) => {
+                lyrics_text(&text, state.settings.theme.timestamp())
+            }
+            Some(Lyrics::Instrumental) => Text::raw("Instrumental"),
+            Some(Lyrics::None) | None => Text::raw(""),
+        };
+        Paragraph::new(preview)
+            .wrap(Wrap { trim: false })
+            .block(panel(state).title("Preview"))
+            .render(preview_area, buf);
+        let remembered = state.releases.get(&release_key(&data));
+        let rows = state.candidates.iter().map(|candidate| {
+            let marker = if remembered == Some(&candidate.album_name) {
+                "*"
+            } else {
+                ""
+            };
+            let seconds = candidate.duration.round() as i64;
+            let offset = seconds - data.duration as i64;
+            let kind = match candidate.lyrics().status() {
+                LyricsStatus::Synced => "synced",
+                LyricsStatus::Plain => "plain",
+                LyricsStatus::Instrumental => "instrumental",
+                LyricsStatus::None => "none",
+            };
+            Row::new([
+                marker.to_string(),
+                candidate.album_name.clone(),
+                candidate.artist_name.clone(),
+                format!("{}:{:02} ({offset:+}s)", seconds / 60, seconds % 60),
+                candidate.id.to_string(),
+                match (candidate.source, candidate.variant) {
+                    (Some(source), Some(variant)) => format!("{source} ({variant})"),
+                    (source, _) => source.unwrap_or("lrclib").to_string(),
+                },
+                kind.to_string(),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Length(1),
+                Fill(2),
+                Fill(1),
+                Length(14),
+                Length(9),
+                Length(22),
+                Length(12),
+            ],
+        )
+        .header(Row::new([
+            "", "Release", "Artist", "Duration", "Id", "Source", "Lyrics",
+        ]))
+        .row_highlight_style(state.settings.theme.highlight());
+        StatefulWidget::render(table, inner, buf, &mut state.candidates_state);
+    }
+    fn render_debug_overlay(&self, area: Rect, buf: &mut Buffer, state: &mut State) {
+        use ratatui::layout::Constraint::Length;
+        use ratatui::layout::Flex::End;
+
+        let lines = [
+            format!("frame: {:.2?}", state.debug.frame_time),
+            format!("input: {:.2?}", state.debug.event_latency),
+            format!("fetching: {}", state.api_joins.len()),
+            format!("queued: {}", state.fetch_queue.len()),
+            format!(
+                "writing: {}",
+                state.write_joins.len() + state.lyric_writes.len()
+            ),
+            format!("previews: {}", state.preview_joins.len()),
+            format!("ua: {}", user_agent(state.settings.contact.as_deref())),
+        ];
+        let width = lines.iter().map(|line| line.len()).max().unwrap_or(0) as u16 + 2;
+        let [area] = Layout::vertical([Length(lines.len() as u16 + 2)])
+            .flex(End)
+            .areas(area);
+        let [area] = Layout::horizontal([Length(width)]).flex(End).areas(area);
+        Clear.render(area, buf);
+        let block = panel(state).title("Debug");
+        let txt = Text::raw(lines.join("\n"));
+        txt.render(block.inner(area), buf);
+        block.render(area, buf);
+    }
+    fn render_limiter_panel(&self, area: Rect, buf: &mut Buffer, state: &mut State) {
+        use ratatui::layout::Constraint::Length;
+        use ratatui::layout::Flex::{End, Start};
+
+        let stats = state.lrclib_throttle.stats();
+        let in_use = state
+            .settings
+            .concurrent_queries
+            .saturating_sub(state.client_limiter.available_permits());
+        let backoff = if stats.blocked_for.is_zero() {
+            if stats.backoff.is_zero() {
+                "none".to_string()
+            } else {
+                format!("{:.1?} (easing)", stats.backoff)
+            }
+        } else {
+            format!("{:.1?} ({:.1?} left)", stats.backoff, stats.blocked_for)
+        };
+        let lines = [
+            format!(
+                "permits: {in_use}/{} in use",
+                state.settings.concurrent_queries
+            ),
+            format!("tokens: {:.1}/{}", stats.tokens, stats.capacity),
+            format!("backoff: {backoff}"),
+            format!("429s (last minute): {}", stats.recent_rate_limits),
+            format!(
+                "requests: {}",
+                requests_summary(&state.providers.requests())
+            ),
+            format!(
+                "fetch concurrency: {}",
+                state
+                    .providers
+                    .concurrency(state.settings.concurrent_queries.max(1))
+            ),
+        ];
+        let width = lines.iter().map(|line| line.len()).max().unwrap_or(0) as u16 + 2;
+        let [area] = Layout::vertical([Length(lines.len() as u16 + 2)])
+            .flex(End)
+            .areas(area);
+        let [area] = Layout::horizontal([Length(width)]).flex(Start).areas(area);
+        Clear.render(area, buf);
+        let block = panel(state).title("Rate limit");
+        let txt = Text::raw(lines.join("\n"));
+        txt.render(block.inner(area), buf);
+        block.render(area, buf);
+    }
+    fn render_announcements(&self, area: Rect, buf: &mut Buffer, state: &mut State) {
+        let txt = Text::raw(
+            state
+                .announcements
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("\n"),
+        );
+        txt.render(area, buf);
+        // Screen readers follow the cursor, so park it on the newest announcement.
+        state.cursor = (!state.announcements.is_empty()).then(|| {
+            ratatui::layout::Position::new(area.x, area.y + state.announcements.len() as u16 - 1)
+        });
+    }
+    fn render_too_small(&self, area: Rect, buf: &mut Buffer) {
+        use ratatui::layout::Constraint::Length;
+        use ratatui::layout::Flex::Center;
+
+        Clear.render(area, buf);
+        let [text_area] = Layout::vertical([Length(2)]).flex(Center).areas(area);
+        let txt = Paragraph::new(format!(
+            "Terminal too small ({}x{})\nResize to at least {}x{}",
+            area.width, area.height, MIN_WIDTH, MIN_HEIGHT
+        ))
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+        txt.render(text_area, buf);
+    }
+    fn render_text_input(&self, area: Rect, buf: &mut Buffer, state: &mut State) {
+        use ratatui::layout::Constraint::{Length, Percentage};
+        use ratatui::layout::Flex::Center;
+
+        if matches!(state.field, Some(Fields::Paste)) {
+            let [area] = Layout::vertical([Percentage(60)]).flex(Center).areas(area);
+            let [area] = Layout::horizontal([Percentage(70)])
+                .flex(Center)
+                .areas(area);
+            Clear.render(area, buf);
+            let border = panel(state)
+                .title("Paste lyrics or type a file path - enter saves, esc cancels")
+                .title_alignment(Alignment::Center);
+            let inner = border.inner(area);
+            border.render(area, buf);
+            Text::raw(state.current_string.as_str()).render(inner, buf);
+            return;
+        }
+
+        let [area] = Layout::vertical([Length(3)]).flex(Center).areas(area);
+        let [area] = Layout::horizontal([Percentage(50)])
+            .flex(Center)
+            .areas(area);
+        Clear.render(area, buf);
+        let border = panel(state)
+            .title("Input")
+            .title_alignment(Alignment::Center);
+        let inner = border.inner(area);
+        border.render(area, buf);
+        let text = Text::raw(state.current_string.as_str()).centered();
+        text.render(inner, buf);
+    }
+    fn render_filters_popup(&self, area: Rect, buf: &mut Buffer, state: &mut State) {
+        use ratatui::layout::Constraint::{Length, Percentage};
+        use ratatui::layout::Flex::Center;
+
+        let [area] = Layout::vertical([Length(9)]).flex(Center).areas(area);
+        let [area] = Layout::horizontal([Percentage(50)])
+            .flex(Center)
+            .areas(area);
+        Clear.render(area, buf);
+        let border = panel(state)
+            .title("Filters")
+            .title_alignment(Alignment::Center);
+        let inner = border.inner(area);
+        border.render(area, buf);
+        let list = state
+            .filter
+            .to_widget()
+            .highlight_style(state.settings.theme.highlight());
+        StatefulWidget::render(list, inner, buf, &mut state.filters_popup_state);
+    }
+}
+
+/// Borderless in screen reader mode so box drawing characters are not read out.
+fn panel(state: &State) -> Block<'static> {
+    if state.settings.screen_reader {
+        Block::new()
+    } else {
+        Block::bordered()
+    }
+}
+
+/// Lyrics with their leading timestamp tags set off in `tag_style`.
+fn lyrics_text(text: &str, tag_style: Style) -> Text<'static> {
+    text.lines()
+        .map(|line| {
+            let (tags, rest) = lrc::leading_tags(line);
+            if tags.is_empty() {
+                return Line::raw(line.to_string());
+            }
+            let tags = tags
+                .iter()
+                .map(|tag| format!("[{tag}]"))
+                .collect::<String>();
+            Line::from(vec![
+                Span::styled(tags, tag_style),
+                Span::raw(rest.to_string()),
+            ])
+        })
+        .collect()
+}
+
+impl StatefulWidget for Screen<'_> {
+    type State = State;
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        use ratatui::layout::Constraint::{Fill, Length, Min};
+
+        if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+            self.render_too_small(area, buf);
+            return;
+        }
+        let announcements_height = if state.settings.screen_reader {
+            ANNOUNCEMENT_LINES as u16
+        } else {
+            0
+        };
+        let vertical = Layout::vertical([
+            Length(1),
+            Min(0),
+            Length(1),
+            Length(announcements_height),
+            Length(1),
+        ]);
+        let [
+            title_area,
+            main_area,
+            progress_area,
+            announcements_area,
+            status_area,
+        ] = vertical.areas(area);
+        let [tracks_area, lyrics_area] = match state.settings.layout {
+            LayoutMode::Compact => [main_area, Rect::default()],
+            LayoutMode::Split if area.width < STACKED_WIDTH => {
+                Layout::vertical([Fill(1); 2]).areas(main_area)
+            }
+            LayoutMode::Split => Layout::horizontal([Fill(1); 2]).areas(main_area),
+        };
+        let block = panel(state).title("Tracks");
+        StatefulWidget::render(
+            self.tracks.clone(),
+            block.inner(tracks_area),
+            buf,
+            &mut state.table_state,
+        );
+        block.render(tracks_area, buf);
+        if state.settings.screen_reader {
+            self.render_announcements(announcements_area, buf, state);
+        }
+        if state.scanning() {
+            let progress = &state.scan_progress;
+            Text::raw(format!(
+                "Scanning: {} files found, {} tags read, {} lyrics checked",
+                progress.discovered.load(Ordering::Relaxed),
+                progress.loaded.load(Ordering::Relaxed),
+                progress.checked.load(Ordering::Relaxed)
+            ))
+            .render(progress_area, buf);
+        } else if state.settings.screen_reader {
+            let rate = match state.fetch_rate() {
+                Some((rate, eta)) => format!(" ({rate:.1} per second, about {eta} left)"),
+                None => String::new(),
+            };
+            Text::raw(format!(
+                "Fetched {} of {}{rate}, wrote {} of {}",
+                state.done, state.total, state.writes_done, state.writes_total
+            ))
+            .render(progress_area, buf);
+        } else {
+            let [fetch_area, write_area] = Layout::horizontal([Fill(1); 2])
+                .spacing(1)
+                .areas(progress_area);
+            let mut label = format!("fetch {}/{}", state.done, state.total);
+            if let Some((rate, eta)) = state.fetch_rate() {
+                label.push_str(&format!(", {rate:.1}/s, ETA {eta}"));
+            }
+            gauge(label, state.done, state.total).render(fetch_area, buf);
+            let label = format!("write {}/{}", state.writes_done, state.writes_total);
+            gauge(label, state.writes_done, state.writes_total).render(write_area, buf);
+        }
+        let mut title = match &state.recording {
+            Some(actions) => format!("LRC Fetch [recording macro: {} actions]", actions.len()),
+            None if !state.marked.is_empty() => {
+                format!("LRC Fetch [{} marked]", state.marked.len())
+            }
+            None => "LRC Fetch".to_string(),
+        };
+        match state.settings.lyrics_output {
+            LyricsOutput::Sidecar => {}
+            LyricsOutput::Embedded => title.push_str(" [embedding lyrics]"),
+            LyricsOutput::Both => title.push_str(" [sidecars + embedding]"),
+        }
+        if !state.failures.is_empty() {
+            title.push_str(&format!(" [{} failed]", state.failures.len()));
+        }
+        if state.scanning() {
+            title.push_str(" [scanning...]");
+        }
+        if !state.resumable.is_empty() {
+            title.push_str(&format!(" [{} to resume: u]", state.resumable.len()));
+        }
+        if !state.review.is_empty() {
+            title.push_str(&format!(" [{} to review: n]", state.review.len()));
+        }
+        if *state.pause.borrow() {
+            title.push_str(" [paused: p]");
+        }
+        if state.settings.preview_only {
+            title.push_str(&format!(" [preview only: {} unsaved]", state.unsaved.len()));
+        }
+        let txt = Text::raw(title).alignment(Alignment::Center);
+        txt.render(title_area, buf);
+        let txt = Text::raw(
+            "q - quit, j - down, k - up, l - lyrics, J/K - scroll lyrics, i - details, c - candidates, x - unqueue, Esc - cancel fetches, p - pause, b - queue, X - extract embedded, P - missing playlist, ? - what's new, e - failures, R - retry failed, h - activity, u - resume queue, n - review, o - newest first, F5 - rescan, F - folders, g - flag, V - paste lyrics, w/W - save previewed, r - rate limit, v - layout, space - mark, Q - record, @ - replay",
+        )
+        .alignment(Alignment::Center);
+        txt.render(status_area, buf);
+        if state.settings.layout == LayoutMode::Split {
+            self.render_lyrics(lyrics_area, buf, state);
+        }
+        if state.field.is_some() {
+            self.render_text_input(area, buf, state);
+        } else if state.screen == Screens::Filters {
+            self.render_filters_popup(area, buf, state);
+        } else if state.screen == Screens::Lyrics {
+            self.render_lyrics_popup(area, buf, state);
+        } else if state.screen == Screens::Failures {
+            self.render_failures_popup(area, buf, state);
+        } else if state.screen == Screens::Candidates {
+            self.render_candidates_popup(area, buf, state);
+        } else if state.screen == Screens::Details {
+            self.render_details_popup(area, buf, state);
+        } else if state.screen == Screens::AlbumApply {
+            self.render_album_apply_popup(area, buf, state);
+        } else if state.screen == Screens::WhatsNew {
+            self.render_whats_new_popup(area, buf, state);
+        } else if state.screen == Screens::Activity {
+            self.render_activity_popup(area, buf, state);
+        } else if state.screen == Screens::Folders {
+            self.render_folders_popup(area, buf, state);
+        } else if state.screen == Screens::Queue {
+            self.render_queue_popup(area, buf, state);
+        }
+        if state.limiter_panel {
+            self.render_limiter_panel(area, buf, state);
+        }
+        if state.debug.visible {
+            self.render_debug_overlay(area, buf, state);
+        }
+    }
+}
+
+/// A rescanned library and the lyrics status of each track in it.
+type Rescan = (
+    Vec<Arc<MusicData>>,
+    HashMap<PathBuf, LyricsStatus>,
+    Option<IndexUpdate>,
+);
+type FolderScan = (
+    PathBuf,
+    Vec<Arc<MusicData>>,
+    HashMap<PathBuf, LyricsStatus>,
+    Option<IndexUpdate>,
+);
+type Fetch = Result<(LyricsRecord, Vec<Scored>), QueryError>;
+/// The candidates for a track, and whether every source answered so they can be cached.
+type Search = (Arc<MusicData>, Result<Vec<Candidate>, QueryError>, bool);
+
+struct State {
+    settings: Settings,
+    screen: Screens,
+    table_state: TableState,
+    will_quit: bool,
+    /// Set once quitting was refused over unsaved lyrics; the next quit goes through.
+    quit_warned: bool,
+    music: Vec<Arc<MusicData>>,
+    /// When the library was last walked; files modified after it are re-read on rescan.
+    scanned_at: SystemTime,
+    rescan_joins: JoinSet<Rescan>,
+    lyrics: HashMap<PathBuf, LyricsStatus>,
+    sources: HashMap<PathBuf, &'static str>,
+    outcomes: HashMap<PathBuf, FetchOutcome>,
+    preview_cache: LruCache<PathBuf, Lyrics>,
+    /// Lyrics fetched in preview-only mode that have not been written yet.
+    unsaved: HashMap<PathBuf, Lyrics>,
+    preview_joins: tokio::task::JoinSet<(PathBuf, Result<Lyrics, tokio::io::Error>)>,
+    preview_pending: HashSet<PathBuf>,
+    /// Why reading a preview failed, so it isn't retried every frame. Cleared by a rescan.
+    preview_failed: HashMap<PathBuf, String>,
+    total: usize,
+    done: usize,
+    /// When recent fetches finished, within `RATE_WINDOW`.
+    completions: VecDeque<Instant>,
+    api_joins: tokio::task::JoinSet<Fetch>,
+    fetch_queue: VecDeque<Arc<MusicData>>,
+    /// Each waiting track's place in `fetch_queue` for the track table, dropped whenever the
+    /// queue changes and rebuilt at the next draw.
+    queued_at: Option<HashMap<PathBuf, usize>>,
+    /// Queued tracks whose fetch should ask the providers again instead of reusing a cached
+    /// response, because they were fetched or retried by hand.
+    bypass_cache: HashSet<PathBuf>,
+    fetch_tasks: HashMap<tokio::task::Id, InFlight>,
+    queue_state: ListState,
+    /// Tracks left over from a batch that was interrupted last session.
+    resumable: Vec<PathBuf>,
+    review: Review,
+    queue_saved: (usize, usize),
+    queue_saved_at: Instant,
+    failures: Vec<Failure>,
+    failures_state: ListState,
+    retried: HashSet<PathBuf>,
+    retrying: bool,
+    write_joins: tokio::task::JoinSet<Result<usize, tokio::io::Error>>,
+    /// Lyrics file writes, counted apart from state files for the write gauge.
+    lyric_writes: JoinSet<Result<usize, tokio::io::Error>>,
+    /// Player hooks that failed after a write, reported apart from it since the lyrics are saved.
+    hook_failed: tokio::sync::mpsc::UnboundedSender<(PathBuf, String)>,
+    hook_failures: tokio::sync::mpsc::UnboundedReceiver<(PathBuf, String)>,
+    writes_total: usize,
+    writes_done: usize,
+    client: reqwest::Client,
+    client_limiter: Arc<Semaphore>,
+    /// Whether fetching is paused; dispatched fetches wait on it before taking a request permit.
+    pause: watch::Sender<bool>,
+    lrclib_throttle: Arc<Throttle>,
+    providers: Arc<Registry>,
+    limiter_panel: bool,
+    marked: HashSet<PathBuf>,
+    recording: Option<Vec<Func>>,
+    recorded_macro: Vec<Func>,
+    candidates: Vec<Candidate>,
+    candidates_for: Option<Arc<MusicData>>,
+    candidates_state: TableState,
+    candidates_status: Option<String>,
+    search_joins: JoinSet<Search>,
+    /// Candidate lists by normalized title and artist, so reopening the picker is free.
+    search_cache: LruCache<(String, Arc<str>), Vec<Candidate>>,
+    trash_joins: JoinSet<Option<Arc<MusicData>>>,
+    /// Tracks whose flagged sidecars were trashed, waiting for the candidate picker.
+    refetch_queue: VecDeque<Arc<MusicData>>,
+    releases: HashMap<(String, String), String>,
+    /// The lyric variant last picked from each provider, listed first next time.
+    variant_preferences: HashMap<String, String>,
+    duration_overrides: HashMap<PathBuf, usize>,
+    flags: HashMap<PathBuf, TrackFlag>,
+    misses: Misses,
+    duration_joins: JoinSet<(PathBuf, Option<usize>)>,
+    details_status: Option<String>,
+    album_apply: Option<AlbumApply>,
+    whats_new_scroll: u16,
+    /// Lyrics pane offsets per track for this session, so switching tracks keeps each place.
+    lyrics_scroll: HashMap<PathBuf, u16>,
+    file_limiter: Arc<Semaphore>,
+    local_limiter: Arc<Semaphore>,
+    filter: Filter,
+    applied_filter: Filter,
+    filtered: Vec<usize>,
+    /// Newest additions first instead of album order.
+    sort_added: bool,
+    /// The theme from an imported preset, which wins over the config file.
+    theme_override: Option<Theme>,
+    field: Option<Fields>,
+    current_string: String,
+    filters_popup_state: ListState,
+    stats: Stats,
+    /// Counts for the fetch batch in progress, for the notification when it finishes.
+    batch: Stats,
+    /// Every track queued in the batch, once each however often it was retried.
+    batch_tracks: HashSet<PathBuf>,
+    debug: DebugInfo,
+    announcements: VecDeque<String>,
+    /// Recent actions, newest first, for the activity screen.
+    activity: VecDeque<Activity>,
+    activity_state: ListState,
+    folders: Vec<Folder>,
+    folders_state: ListState,
+    /// With `lazy_scan`, the folders whose tags have been read this session.
+    loaded_folders: HashSet<PathBuf>,
+    folder_joins: JoinSet<FolderScan>,
+    /// The tags as last read, shared with running scans. Only the main loop merges their
+    /// results and saves it, so concurrent folder scans can't overwrite each other.
+    index: Arc<Index>,
+    /// Tracks from the initial scan as they are read, shown before the sorted library is ready.
+    scan_found: Option<tokio::sync::mpsc::UnboundedReceiver<Arc<MusicData>>>,
+    scan_progress: Arc<ScanProgress>,
+    /// Folders to fetch for as soon as their tags are loaded.
+    fetch_after_load: HashSet<PathBuf>,
+    announced_selection: Option<PathBuf>,
+    announced_screen: Screens,
+    cursor: Option<ratatui::layout::Position>,
+}
+
+struct AlbumApply {
+    album: Arc<str>,
+    tracks: Vec<Arc<MusicData>>,
+}
+
+/// How the last fetch for a track ended, so "no lyrics" can be told apart from a failure.
+enum FetchOutcome {
+    NotFound,
+    /// Only borderline matches turned up; they wait in the review queue.
+    NeedsReview,
+    Error(String),
+    Found(LyricsStatus),
+}
+
+struct Activity {
+    at: Instant,
+    message: String,
+    path: Option<PathBuf>,
+}
+
+/// A directory in the library, counting every track beneath it.
+struct Folder {
+    path: PathBuf,
+    depth: usize,
+    tracks: usize,
+    unsynced: usize,
+    loaded: bool,
+}
+
+/// A fetch that has been dispatched, for the queue screen.
+struct InFlight {
+    data: Arc<MusicData>,
+    started: Instant,
+    abort: tokio::task::AbortHandle,
+}
+
+struct Failure {
+    data: Arc<MusicData>,
+    reason: String,
+    transient: bool,
+}
+
+#[derive(Default)]
+struct DebugInfo {
+    visible: bool,
+    frame_time: Duration,
+    event_latency: Duration,
+    event_at: Option<Instant>,
+}
+
+#[derive(Default)]
+struct Stats {
+    scanned: usize,
+    synced: usize,
+    plain: usize,
+    instrumental: usize,
+    not_found: usize,
+    /// Only borderline matches were found, so the track went to the review queue.
+    borderline: usize,
+    errors: usize,
+    bytes_written: usize,
+    /// Requests per provider, filled in from the registry for the summary.
+    requests: Vec<(&'static str, usize)>,
+}
+
+impl Stats {
+    fn record_lyrics(&mut self, lyrics: &Lyrics) {
+        self.scanned += 1;
+        match lyrics {
+            Lyrics::None => self.not_found += 1,
+            Lyrics::Synced(_) => self.synced += 1,
+            Lyrics::Plain(_) => self.plain += 1,
+            Lyrics::Instrumental => self.instrumental += 1,
+        }
+    }
+    fn record_borderline(&mut self) {
+        self.scanned += 1;
+        self.borderline += 1;
+    }
+    fn record_write(&mut self, result: Result<usize, tokio::io::Error>) {
+        match result {
+            Ok(bytes) => self.bytes_written += bytes,
+            Err(_) => self.errors += 1,
+        }
+    }
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "lrcfetch session summary")?;
+        writeln!(f, "  tracks scanned: {}", self.scanned)?;
+        writeln!(
+            f,
+            "  lyrics fetched: {} synced, {} plain, {} instrumental, {} not found, {} to review",
+            self.synced, self.plain, self.instrumental, self.not_found, self.borderline
+        )?;
+        writeln!(f, "  errors: {}", self.errors)?;
+        writeln!(f, "  API requests: {}", requests_summary(&self.requests))?;
+        write!(
+            f,
+            "  data written: {:.1} KiB",
+            self.bytes_written as f64 / 1024.0
+        )
+    }
+}
+
+#[derive(Clone)]
+enum Fields {
+    Title,
+    Artist,
+    Album,
+    Duration,
+    Added,
+    /// Lyrics pasted into the terminal, or the path of a file holding them.
+    Paste,
+}
+
+impl State {
+    fn event_handler(&mut self, event: Event, keymap: &HashMap<KeyBind, Func>) {
+        if let Event::FocusGained = event
+            && self.settings.rescan_on_focus
+        {
+            self.rescan();
+            return;
+        }
+        if let Event::Paste(text) = &event
+            && matches!(self.field, Some(Fields::Paste))
+        {
+            self.current_string.push_str(&text.replace("\r\n", "\n"));
+            return;
+        }
+        if let Event::Key(event) = event {
+            if !event.is_press() {
+                return;
+            }
+            match self.field.clone() {
+                Some(Fields::Duration) => match event.code {
+                    KeyCode::Enter => {
+                        let duration = self.current_string.parse::<usize>().ok();
+                        self.current_string.clear();
+                        self.field = None;
+                        if let Some(path) = self.selected_music().map(|data| data.path.clone()) {
+                            self.set_duration_override(path, duration);
+                        }
+                    }
+                    KeyCode::Char(c) if c.is_ascii_digit() => self.current_string.push(c),
+                    KeyCode::Backspace => {
+                        self.current_string.pop();
+                    }
+                    KeyCode::Esc => {
+                        self.current_string.clear();
+                        self.field = None;
+                    }
+                    _ => {}
+                },
+                Some(Fields::Paste) => match event.code {
+                    KeyCode::Enter => {
+                        let input = std::mem::take(&mut self.current_string);
+                        self.field = None;
+                        self.save_pasted(input);
+                    }
+                    KeyCode::Char(c) => self.current_string.push(c),
+                    KeyCode::Backspace => {
+                        self.current_string.pop();
+                    }
+                    KeyCode::Esc => {
+                        self.current_string.clear();
+                        self.field = None;
+                    }
+                    _ => {}
+                },
+                Some(field) => match event.code {
+                    KeyCode::Enter => {
+                        let str = if self.current_string.is_empty() {
+                            None
+                        } else {
+                            let res = Some(self.current_string.clone());
+                            self.current_string = String::new();
+                            res
+                        };
+                        let artist = matches!(field, Fields::Artist);
+                        self.set_field(field, str);
+                        self.field = None;
+                        self.update_filter();
+                        if artist {
+                            self.load_filtered_folders();
+                        }
+                    }
+                    KeyCode::Char(c) => {
+                        self.current_string.push(c);
+                        self.set_field(field, Some(self.current_string.clone()));
+                        self.update_filter();
+                    }
+                    KeyCode::Backspace => {
+                        self.current_string.pop();
+                        let str = if self.current_string.is_empty() {
+                            None
+                        } else {
+                            Some(self.current_string.clone())
+                        };
+                        self.set_field(field, str);
+                        self.update_filter();
+                    }
+                    _ => {}
+                },
+                None => {
+                    let code = KeyBind {
+                        screen: self.screen,
+                        keycode: event.code,
+                    };
+                    if let Some(func) = keymap.get(&code) {
+                        if let Some(actions) = &mut self.recording
+                            && func.recordable()
+                        {
+                            actions.push(*func);
+                        }
+                        func.call(self);
+                    }
+                }
+            }
+        }
+    }
+    fn announce(&mut self, message: String) {
+        if !self.settings.screen_reader {
+            return;
+        }
+        self.announcements.push_back(message);
+        while self.announcements.len() > ANNOUNCEMENT_LINES {
+            self.announcements.pop_front();
+        }
+    }
+    /// Saves pasted lyrics, or the contents of the file they name, as the selected track's.
+    fn save_pasted(&mut self, input: String) {
+        let Some(data) = self.selected_music().cloned() else {
+            return;
+        };
+        // A single line naming an existing file, relative to the track's folder, is read;
+        // anything else, such as a one-line plain lyric, is the lyrics themselves.
+        let path = input.trim();
+        let file = data
+            .path
+            .parent()
+            .map_or_else(|| PathBuf::from(path), |dir| dir.join(path));
+        let text = if !path.is_empty() && !path.contains('\n') && file.is_file() {
+            match std::fs::read_to_string(&file) {
+                Ok(text) => text,
+                Err(err) => {
+                    self.record(
+                        format!("Could not read {}: {err}", file.display()),
+                        Some(data.path.clone()),
+                    );
+                    return;
+                }
+            }
+        } else {
+            input
+        };
+        if text.trim().is_empty() {
+            return;
+        }
+        let report = lrc::validate(&text);
+        let (lyrics, note) = if report.timed_lines == 0 {
+            (Lyrics::Plain(text), String::new())
+        } else if report.invalid_lines.is_empty() {
+            (Lyrics::Synced(text), String::new())
+        } else {
+            let note = format!(" ({} malformed lines)", report.invalid_lines.len());
+            (Lyrics::Synced(text), note)
+        };
+        let status = lyrics.status();
+        self.announce(format!("Saved {status} lyrics for {}{note}", data.title));
+        self.record(
+            format!("Saved pasted {status} lyrics for {}{note}", data.title),
+            Some(data.path.clone()),
+        );
+        let record = LyricsRecord {
+            lyrics,
+            path: data.path.clone(),
+            source: Some("manual"),
+        };
+        record.save(self);
+        self.sources.insert(record.path.clone(), "manual");
+        self.lyrics.insert(record.path.clone(), status);
+        self.preview_cache.put(record.path, record.lyrics);
+    }
+    fn record(&mut self, message: String, path: Option<PathBuf>) {
+        self.activity.push_front(Activity {
+            at: Instant::now(),
+            message,
+            path,
+        });
+        self.activity.truncate(ACTIVITY_LIMIT);
+    }
+    fn describe(&self, data: &MusicData) -> String {
+        let mut description = format!(
+            "{} by {}, album {}, {} seconds, lyrics {}",
+            data.title,
+            data.artist,
+            data.album,
+            data.duration,
+            StatusIcons::Text.label(self.lyrics.get(&data.path).copied())
+        );
+        if let Some(position) = self
+            .fetch_queue
+            .iter()
+            .position(|queued| queued.path == data.path)
+        {
+            description.push_str(&format!(", queued at {}", position + 1));
+        }
+        if self.marked.contains(&data.path) {
+            description.push_str(", marked");
+        }
+        description
+    }
+    /// Announces selection and screen changes since the last call.
+    fn announce_changes(&mut self) {
+        if self.screen != self.announced_screen {
+            self.announced_screen = self.screen;
+            self.announce(format!("{} screen", self.screen.name()));
+        }
+        let selected = self.selected_music().cloned();
+        if selected.as_ref().map(|data| &data.path) != self.announced_selection.as_ref() {
+            self.announced_selection = selected.as_ref().map(|data| data.path.clone());
+            if let Some(data) = selected {
+                let description = self.describe(&data);
+                self.announce(description);
+            }
+        }
+    }
+    fn selected_music(&self) -> Option<&Arc<MusicData>> {
+        let selected = self.table_state.selected()?;
+        self.filtered.get(selected).map(|&index| &self.music[index])
+    }
+    fn filtered_music(&self) -> impl Iterator<Item = &Arc<MusicData>> {
+        self.filtered.iter().map(|&index| &self.music[index])
+    }
+    fn refilter(&mut self) {
+        let keys = self.filter.keys();
+        self.filtered = (0..self.music.len())
+            .filter(|&index| keys.matches(&self.music[index]) && self.flag_matches(index))
+            .collect();
+        if self.sort_added {
+            let music = &self.music;
+            self.filtered
+                .sort_by_key(|&index| std::cmp::Reverse(music[index].added));
+        }
+        self.applied_filter = self.filter.clone();
+    }
+    fn update_filter(&mut self) {
+        if self.filter == self.applied_filter {
+            return;
+        }
+        if self.filter.narrows(&self.applied_filter) {
+            let keys = self.filter.keys();
+            let filtered = std::mem::take(&mut self.filtered);
+            self.filtered = filtered
+                .into_iter()
+                .filter(|&index| keys.matches(&self.music[index]) && self.flag_matches(index))
+                .collect();
+            self.applied_filter = self.filter.clone();
+        } else {
+            self.refilter();
+        }
+    }
+    /// Scans the whole library in the background, replacing any scan already running, so the
+    /// interface is usable while a large or remote library is read.
+    fn scan(&mut self) {
+        let settings = self.settings.clone();
+        let limiter = self.local_limiter.clone();
+        self.scanned_at = SystemTime::now();
+        self.preview_failed.clear();
+        self.rescan_joins = JoinSet::new();
+        self.folder_joins = JoinSet::new();
+        self.loaded_folders.clear();
+        let progress = self.scan_progress();
+        let (found, receiver) = tokio::sync::mpsc::unbounded_channel();
+        self.scan_found = Some(receiver);
+        self.music.clear();
+        self.refilter();
+        let index = self.index.clone();
+        self.rescan_joins.spawn(async move {
+            let (music, update) = if settings.lazy_scan {
+                (index.music(&settings), None)
+            } else {
+                let root = settings.music_path.clone();
+                read_folder(&settings, &root, &index, &progress, |data| {
+                    let _ = found.send(Arc::new(data.clone()));
+                })
+                .await
+            };
+            let lyrics = check_lyrics_counted(&music, limiter, &progress.checked).await;
+            (music, lyrics, update)
+        });
+    }
+    /// The counters for a new scan, shared with any scan still running.
+    fn scan_progress(&mut self) -> Arc<ScanProgress> {
+        if !self.scanning() {
+            self.scan_progress = Arc::default();
+        }
+        self.scan_progress.clone()
+    }
+    fn scanning(&self) -> bool {
+        !self.rescan_joins.is_empty() || !self.folder_joins.is_empty()
+    }
+    /// Rescans the library in the background, only re-reading files changed since the last scan.
+    fn rescan(&mut self) {
+        if !self.rescan_joins.is_empty() {
+            return;
+        }
+        self.preview_failed.clear();
+        let settings = self.settings.clone();
+        let previous = self.music.clone();
+        let since = self.scanned_at;
+        let limiter = self.local_limiter.clone();
+        let progress = self.scan_progress();
+        self.scanned_at = SystemTime::now();
+        self.rescan_joins.spawn(async move {
+            let music = rescan_music(&settings, &previous, since, &progress).await;
+            let lyrics = check_lyrics_counted(&music, limiter, &progress.checked).await;
+            (music, lyrics, None)
+        });
+    }
+    /// Every directory holding tracks, with its ancestors up to the library root, in
+    /// filesystem order.
+    fn folders(&self) -> Vec<Folder> {
+        let root = &self.settings.music_path;
+        let mut counts = BTreeMap::<PathBuf, (usize, usize)>::new();
+        if self.settings.lazy_scan {
+            for folder in top_folders(&self.settings) {
+                counts.insert(folder, (0, 0));
+            }
+        }
+        for data in &self.music {
+            let unsynced = !matches!(
+                self.lyrics.get(&data.path),
+                Some(LyricsStatus::Synced | LyricsStatus::Instrumental)
+            );
+            for dir in data.path.ancestors().skip(1) {
+                if dir == root || !dir.starts_with(root) {
+                    break;
+                }
+                let (tracks, missing) = counts.entry(dir.to_path_buf()).or_default();
+                *tracks += 1;
+                *missing += usize::from(unsynced);
+            }
+        }
+        counts
+            .into_iter()
+            .map(|(path, (tracks, unsynced))| Folder {
+                depth: path
+                    .strip_prefix(root)
+                    .map_or(0, |rel| rel.components().count() - 1),
+                loaded: !self.settings.lazy_scan || self.folder_loaded(&path),
+                path,
+                tracks,
+                unsynced,
+            })
+            .collect()
+    }
+    fn folder_loaded(&self, folder: &Path) -> bool {
+        folder
+            .ancestors()
+            .any(|dir| self.loaded_folders.contains(dir))
+    }
+    /// With `lazy_scan`, reads the tags under `folder` in the background unless already done.
+    fn load_folder(&mut self, folder: PathBuf) {
+        if !self.settings.lazy_scan || self.folder_loaded(&folder) {
+            return;
+        }
+        self.loaded_folders.insert(folder.clone());
+        let settings = self.settings.clone();
+        let limiter = self.local_limiter.clone();
+        let progress = self.scan_progress();
+        let index = self.index.clone();
+        self.folder_joins.spawn(async move {
+            let (music, update) = read_folder(&settings, &folder, &index, &progress, |_| {}).await;
+            let lyrics = check_lyrics_counted(&music, limiter, &progress.checked).await;
+            (folder, music, lyrics, update)
+        });
+    }
+    /// With `lazy_scan`, loads the top-level folders named like the artist filter.
+    fn load_filtered_folders(&mut self) {
+        let Some(artist) = self.filter.artist.as_deref().map(fold) else {
+            return;
+        };
+        if !self.settings.lazy_scan {
+            return;
+        }
+        for folder in top_folders(&self.settings) {
+            if folder
+                .file_name()
+                .is_some_and(|name| fold(&name.to_string_lossy()).contains(&artist))
+            {
+                self.load_folder(folder);
+            }
+        }
+    }
+    /// Merges freshly read tracks for `folder` into the library.
+    fn merge_folder(&mut self, folder: &Path, music: &[Arc<MusicData>]) {
+        let selected = self.selected_music().map(|data| data.path.clone());
+        self.music.retain(|data| !data.path.starts_with(folder));
+        self.music.extend(music.iter().cloned());
+        self.music
+            .sort_by(|a, b| a.album_order().cmp(&b.album_order()));
+        self.refilter();
+        if let Some(position) =
+            selected.and_then(|path| self.filtered_music().position(|data| data.path == path))
+        {
+            self.table_state.select(Some(position));
+        }
+    }
+    fn selected_folder(&self) -> Option<PathBuf> {
+        let index = self.folders_state.selected()?;
+        Some(self.folders.get(index)?.path.clone())
+    }
+    fn flag_matches(&self, index: usize) -> bool {
+        self.filter
+            .flag
+            .is_none_or(|flag| self.flags.get(&self.music[index].path) == Some(&flag))
+    }
+    fn show_candidates(&mut self, data: &MusicData, mut candidates: Vec<Candidate>) {
+        // Stable, so other variants just sink below the preferred ones.
+        candidates.sort_by_key(|candidate| match candidate.source {
+            Some(source) if candidate.variant.is_some() => {
+                self.variant_preferences.get(source).map(String::as_str) != candidate.variant
+            }
+            _ => false,
+        });
+        let release = self.releases.get(&release_key(data));
+        self.candidates_state
+            .select(default_candidate(&candidates, data, release));
+        self.candidates_status = candidates.is_empty().then(|| "no results".to_string());
+        self.announce(format!("{} candidates found", candidates.len()));
+        self.candidates = candidates;
+    }
+    fn request_preview(&mut self) {
+        let Some(selected) = self.table_state.selected() else {
+            return;
+        };
+        self.request_preview_at(selected);
+        for offset in 1..=PREFETCH_RADIUS {
+            self.request_preview_at(selected + offset);
+            if let Some(previous) = selected.checked_sub(offset) {
+                self.request_preview_at(previous);
+            }
+        }
+    }
+    fn request_preview_at(&mut self, position: usize) {
+        let Some(item) = self.filtered.get(position).map(|&index| &self.music[index]) else {
+            return;
+        };
+        let Some(status) = self.lyrics.get(&item.path).copied() else {
+            return;
+        };
+        if status.sidecar(&item.path).is_none()
+            || self.preview_pending.contains(&item.path)
+            || self.preview_failed.contains_key(&item.path)
+        {
+            return;
+        }
+        let music = Arc::clone(item);
+        if self.preview_cache.get(&music.path).is_some() {
+            return;
+        }
+        if let Some(lyrics) = self.unsaved.get(&music.path) {
+            self.preview_cache.put(music.path.clone(), lyrics.clone());
+            return;
+        }
+        let semaphore = self.local_limiter.clone();
+        self.preview_pending.insert(music.path.clone());
+        self.preview_joins.spawn(async move {
+            let _lock = semaphore.acquire_owned().await;
+            let lyrics = music.read_lyrics(status).await;
+            (music.path.clone(), lyrics)
+        });
+    }
+    fn dispatch(&mut self) {
+        if *self.pause.borrow() {
+            return;
+        }
+        let limit = if self.retrying {
+            (self.settings.concurrent_queries / 4).max(1)
+        } else {
+            self.settings.concurrent_queries.max(1) * 2
+        };
+        let limit = self.providers.concurrency(limit);
+        while self.api_joins.len() < limit {
+            let Some(data) = self.fetch_queue.pop_front() else {
+                break;
+            };
+            self.queued_at = None;
+            self.spawn_fetch(data);
+        }
+    }
+    fn spawn_fetch(&mut self, data: Arc<MusicData>) {
+        let registry = self.providers.clone();
+        let semaphore = self.client_limiter.clone();
+        let task = Arc::clone(&data);
+        let providers = self.settings.providers_for(&data).to_vec();
+        let options = self.settings.write_options();
+        let bypass_cache = self.bypass_cache.remove(&data.path);
+        let mut paused = self.pause.subscribe();
+        let handle = self.api_joins.spawn(async move {
+            let lock = loop {
+                // The sender lives in `State`, which outlives every fetch task.
+                let _ = paused.wait_for(|paused| !paused).await;
+                let Ok(lock) = semaphore.clone().acquire_owned().await else {
+                    return Err(QueryError::permanent("request limiter closed".to_string()));
+                };
+                // A pause while waiting for the permit holds this fetch back too.
+                if !*paused.borrow() {
+                    break lock;
+                }
+            };
+            let fetched = registry.fetch(&providers, &data, bypass_cache).await?;
+            drop(lock);
+            if let Some(reason) = fetched.lyrics.implausible(&options) {
+                return Err(QueryError::permanent(format!("refused lyrics: {reason}")));
             }
-        }
-        true
+            let record = LyricsRecord {
+                lyrics: fetched.lyrics,
+                path: data.path.clone(),
+                source: fetched.provider,
+            };
+            Ok((record, fetched.review))
+        });
+        self.fetch_tasks.insert(
+            handle.id(),
+            InFlight {
+                data: task,
+                started: Instant::now(),
+                abort: handle,
+            },
+        );
     }
-    fn to_widget(&self) -> List {
-        let mut list = Vec::new();
-        if let Some(title) = &self.title {
-            list.push(Text::raw(format!("Title: {}", title)).centered());
-        } else {
-            list.push(Text::raw(format!("Title:")).centered());
+    /// Dispatched fetches, oldest first; the queue screen lists these before the waiting ones.
+    fn running(&self) -> Vec<(tokio::task::Id, &InFlight)> {
+        let mut running = self
+            .fetch_tasks
+            .iter()
+            .map(|(&id, fetch)| (id, fetch))
+            .collect::<Vec<_>>();
+        running.sort_by_key(|(_, fetch)| fetch.started);
+        running
+    }
+    /// Fetches per second over `RATE_WINDOW` and the time left at that rate, once a batch in
+    /// progress has finished enough fetches to tell.
+    fn fetch_rate(&self) -> Option<(f64, String)> {
+        let recent = self
+            .completions
+            .iter()
+            .skip_while(|at| at.elapsed() > RATE_WINDOW)
+            .collect::<Vec<_>>();
+        let (Some(first), Some(last)) = (recent.first(), recent.last()) else {
+            return None;
+        };
+        let span = last.duration_since(**first).as_secs_f64();
+        if self.done >= self.total || span == 0.0 {
+            return None;
         }
-        if let Some(artist) = &self.artist {
-            list.push(Text::raw(format!("Artist: {}", artist)).centered());
+        let rate = (recent.len() - 1) as f64 / span;
+        let remaining = (self.total - self.done) as f64 / rate;
+        Some((rate, eta(Duration::from_secs_f64(remaining))))
+    }
+    /// Everything still queued, in flight or waiting to be resumed.
+    fn pending_paths(&self) -> Vec<PathBuf> {
+        self.fetch_tasks
+            .values()
+            .map(|fetch| &fetch.data)
+            .chain(&self.fetch_queue)
+            .map(|data| data.path.clone())
+            .chain(self.resumable.iter().cloned())
+            .collect()
+    }
+    /// Persists the pending queue, so a batch cut short by quitting or a crash can be resumed.
+    fn save_queue(&mut self) {
+        let pending = self.pending_paths();
+        self.queue_saved = (self.fetch_queue.len(), self.api_joins.len());
+        self.queue_saved_at = Instant::now();
+        self.write_joins
+            .spawn(async move { store::save(QUEUE_FILE, &pending).await.map(|()| 0) });
+    }
+    /// Moves the selected track's lyrics by one line, stopping at the last line.
+    fn scroll_lyrics(&mut self, down: bool) {
+        let Some(path) = self.selected_music().map(|data| data.path.clone()) else {
+            return;
+        };
+        let lines = match self.preview_cache.peek(&path) {
+            Some(Lyrics::Plain(text) | Lyrics::Synced(text)) => text.lines().count(),
+            _ => 0,
+        };
+        let scroll = self.lyrics_scroll.entry(path).or_default();
+        *scroll = if down {
+            scroll
+                .saturating_add(1)
+                .min(lines.saturating_sub(1).try_into().unwrap_or(u16::MAX))
         } else {
-            list.push(Text::raw(format!("Artist:")).centered());
+            scroll.saturating_sub(1)
+        };
+    }
+    fn save_review(&mut self) {
+        let review = self.review.clone();
+        self.write_joins
+            .spawn(async move { store::save(REVIEW_FILE, &review).await.map(|()| 0) });
+    }
+    fn retry_transient(&mut self) -> bool {
+        let (retry, keep): (Vec<_>, Vec<_>) = std::mem::take(&mut self.failures)
+            .into_iter()
+            .partition(|failure| failure.transient && !self.retried.contains(&failure.data.path));
+        self.failures = keep;
+        if retry.is_empty() {
+            return false;
         }
-        if let Some(album) = &self.album {
-            list.push(Text::raw(format!("Album: {}", album)).centered());
-        } else {
-            list.push(Text::raw(format!("Album:")).centered());
+        for failure in retry {
+            self.retried.insert(failure.data.path.clone());
+            self.fetch_queue.push_back(failure.data);
+            self.queued_at = None;
+            self.total += 1;
         }
-        List::new(list)
-    }
-}
-
-#[derive(Clone)]
-struct Screen<'a> {
-    tracks: Table<'a>,
-}
-
-impl Screen<'_> {
-    fn render_text_input(&self, area: Rect, buf: &mut Buffer, state: &mut State) {
-        use ratatui::layout::Constraint::{Length, Percentage};
-        use ratatui::layout::Flex::Center;
-
-        let [area] = Layout::vertical([Length(3)]).flex(Center).areas(area);
-        let [area] = Layout::horizontal([Percentage(50)])
-            .flex(Center)
-            .areas(area);
-        Clear::default().render(area, buf);
-        let border = Block::bordered()
-            .title("Input")
-            .title_alignment(Alignment::Center);
-        let inner = border.inner(area);
-        border.render(area, buf);
-        let text = Text::raw(state.current_string.as_str()).centered();
-        text.render(inner, buf);
+        self.retrying = true;
+        true
     }
-    fn render_filters_popup(&self, area: Rect, buf: &mut Buffer, state: &mut State) {
-        use ratatui::layout::Constraint::{Length, Percentage};
-        use ratatui::layout::Flex::Center;
-
-        let [area] = Layout::vertical([Length(5)]).flex(Center).areas(area);
-        let [area] = Layout::horizontal([Percentage(50)])
-            .flex(Center)
-            .areas(area);
-        Clear::default().render(area, buf);
-        let border = Block::bordered()
-            .title("Filters")
-            .title_alignment(Alignment::Center);
-        let inner = border.inner(area);
-        border.render(area, buf);
-        let list = state.filter.to_widget().highlight_style(HIGHLIGHT_STYLE);
-        StatefulWidget::render(list, inner, buf, &mut state.filters_popup_state);
+    fn set_field(&mut self, field: Fields, value: Option<String>) {
+        match field {
+            Fields::Title => self.filter.title = value,
+            Fields::Artist => self.filter.artist = value,
+            Fields::Album => self.filter.album = value,
+            Fields::Added => {
+                self.filter.added_within_days = value.and_then(|value| value.parse().ok())
+            }
+            Fields::Duration | Fields::Paste => {}
+        }
     }
-}
-
-impl Default for Screen<'_> {
-    fn default() -> Self {
-        return Screen {
-            tracks: Table::default().row_highlight_style(HIGHLIGHT_STYLE),
+    fn set_duration_override(&mut self, path: PathBuf, duration: Option<usize>) {
+        let Some(index) = self.music.iter().position(|data| data.path == path) else {
+            return;
+        };
+        let duration = match duration {
+            Some(duration) => {
+                self.duration_overrides.insert(path, duration);
+                duration
+            }
+            None => {
+                self.duration_overrides.remove(&path);
+                match MusicData::from_file(path, &mut Interner::default()) {
+                    Ok(data) => data.duration,
+                    Err(_) => self.music[index].duration,
+                }
+            }
         };
+        Arc::make_mut(&mut self.music[index]).duration = duration;
+        self.refilter();
+        let overrides = self.duration_overrides.clone();
+        self.write_joins
+            .spawn(async move { store::save(DURATIONS_FILE, &overrides).await.map(|()| 0) });
     }
 }
 
-impl StatefulWidget for Screen<'_> {
-    type State = State;
-    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
-        use ratatui::layout::Constraint::{Fill, Length, Min};
-
-        let vertical = Layout::vertical([Length(1), Min(0), Length(1), Length(1)]);
-        let [title_area, main_area, progress_area, status_area] = vertical.areas(area);
-        let horizontal = Layout::horizontal([Fill(1); 2]);
-        let [left_area, right_area] = horizontal.areas(main_area);
-        let block = Block::bordered().title("Tracks");
-        StatefulWidget::render(
-            self.tracks.clone(),
-            block.inner(left_area),
-            buf,
-            &mut state.table_state,
-        );
-        block.render(left_area, buf);
-        let progress_bar = widgets::Gauge::default().ratio(if state.total == 0 {
-            1.0
-        } else {
-            state.done as f64 / state.total as f64
-        });
-        progress_bar.render(progress_area, buf);
-        let txt = Text::raw("LRC Fetch").alignment(Alignment::Center);
-        txt.render(title_area, buf);
-        let txt = Text::raw("q - quit, j - down, k - up").alignment(Alignment::Center);
-        txt.render(status_area, buf);
-        let block = Block::bordered().title("Lyrics");
-        'lyrics: {
-            let Some(selected) = state.table_state.selected() else {
-                break 'lyrics;
-            };
-            let Some(item) = state
-                .music
-                .iter()
-                .filter(|x| state.filter.apply(x))
-                .nth(selected)
-            else {
-                break 'lyrics;
-            };
-            if let Some(lyric) = state.lyrics.get(&item.path) {
-                let txt = match lyric {
-                    Lyrics::None => Text::raw("None"),
-                    Lyrics::Instrumental => Text::raw("Instrumental"),
-                    Lyrics::Plain(txt) => Text::raw(txt),
-                    Lyrics::Synced(txt) => Text::raw(txt),
-                };
-                txt.render(block.inner(right_area), buf);
-            } else {
-                let txt = Text::raw("Not found");
-                txt.render(block.inner(right_area), buf);
-            }
-        }
-        block.render(right_area, buf);
-        if let Some(_) = &state.field {
-            self.render_text_input(area, buf, state);
-        } else if state.screen == Screens::Filters {
-            self.render_filters_popup(area, buf, state);
-        }
-    }
+fn gauge(label: String, done: usize, total: usize) -> widgets::Gauge<'static> {
+    let ratio = if total == 0 {
+        1.0
+    } else {
+        done as f64 / total as f64
+    };
+    widgets::Gauge::default().ratio(ratio).label(label)
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Settings {
-    #[serde(default = "default_concurrent")]
-    concurrent_queries: usize,
-    #[serde(default = "default_music_path")]
-    music_path: PathBuf,
+#[cfg(feature = "notify")]
+fn notify_desktop(body: String) {
+    // Best effort: a missing notification daemon should not disturb the interface.
+    tokio::task::spawn_blocking(move || {
+        let _ = notify_rust::Notification::new()
+            .summary("lrcfetch")
+            .body(&body)
+            .show();
+    });
 }
 
-fn default_concurrent() -> usize {
-    50
+#[cfg(not(feature = "notify"))]
+fn notify_desktop(_body: String) {}
+
+fn eta(remaining: Duration) -> String {
+    let seconds = remaining.as_secs();
+    match seconds {
+        0..3600 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        _ => format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        ),
+    }
 }
 
-fn default_music_path() -> PathBuf {
-    if let Ok(Ok(path)) = std::env::var("XDG_MUSIC_DIR").map(|path| absolute(path)) {
-        path
-    } else if let Ok(Ok(path)) =
-        std::env::var("HOME").map(|path| absolute(path).map(|path| path.join("Music")))
-    {
-        path
-    } else if let Ok(path) = current_dir() {
-        path
+fn requests_summary(requests: &[(&str, usize)]) -> String {
+    let used = requests
+        .iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(name, count)| format!("{name} {count}"))
+        .collect::<Vec<_>>();
+    if used.is_empty() {
+        "none".to_string()
     } else {
-        PathBuf::from(".")
+        used.join(", ")
     }
 }
 
-impl Default for Settings {
-    fn default() -> Self {
-        Self {
-            concurrent_queries: 50,
-            music_path: default_music_path(),
-        }
+fn ago(elapsed: Duration) -> String {
+    match elapsed.as_secs() {
+        seconds @ 0..60 => format!("{seconds}s ago"),
+        seconds @ 60..3600 => format!("{}m ago", seconds / 60),
+        seconds => format!("{}h ago", seconds / 3600),
     }
 }
 
-struct State {
-    settings: Settings,
-    screen: Screens,
-    table_state: TableState,
-    will_quit: bool,
-    music: Vec<MusicData>,
-    lyrics: HashMap<PathBuf, Lyrics>,
-    total: usize,
-    done: usize,
-    api_joins: tokio::task::JoinSet<LyricsRecord>,
-    write_joins: tokio::task::JoinSet<Result<(), tokio::io::Error>>,
-    client: reqwest::Client,
-    client_limiter: Arc<Semaphore>,
-    file_limiter: Arc<Semaphore>,
-    filter: Filter,
-    field: Option<Fields>,
-    current_string: String,
-    filters_popup_state: ListState,
+fn search_key(data: &MusicData) -> (String, Arc<str>) {
+    (data.keys.title.clone(), data.keys.artist.clone())
 }
 
-#[derive(Clone)]
-enum Fields {
-    Title,
-    Artist,
-    Album,
+fn release_key(data: &MusicData) -> (String, String) {
+    (data.group_key().to_string(), data.keys.album.to_string())
 }
 
-impl State {
-    fn event_handler(&mut self, event: Event, keymap: &HashMap<KeyBind, Func>) {
-        match event {
-            Event::Key(event) => {
-                if !event.is_press() {
-                    return;
-                }
-                match self.field.clone() {
-                    Some(field) => match event.code {
-                        KeyCode::Enter => {
-                            let str = if self.current_string.is_empty() {
-                                None
-                            } else {
-                                let res = Some(self.current_string.clone());
-                                self.current_string = String::new();
-                                res
-                            };
-                            self.set_field(field, str);
-                            self.field = None;
-                        }
-                        KeyCode::Char(c) => {
-                            self.current_string.push(c);
-                        }
-                        KeyCode::Backspace => {
-                            self.current_string.pop();
-                        }
-                        _ => {}
-                    },
-                    None => {
-                        let code = KeyBind {
-                            screen: self.screen,
-                            keycode: event.code,
-                        };
-                        if let Some(func) = keymap.get(&code) {
-                            func.call(self);
-                        }
-                    }
-                }
-            }
-            _ => {}
-        }
-    }
-    fn set_field(&mut self, field: Fields, value: Option<String>) {
-        match field {
-            Fields::Title => self.filter.title = value,
-            Fields::Artist => self.filter.artist = value,
-            Fields::Album => self.filter.album = value,
-        }
-    }
+fn default_candidate(
+    candidates: &[Candidate],
+    data: &MusicData,
+    release: Option<&String>,
+) -> Option<usize> {
+    candidates
+        .iter()
+        .position(|candidate| Some(&candidate.album_name) == release)
+        .or_else(|| {
+            candidates
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, candidate)| {
+                    (candidate.duration.round() as i64 - data.duration as i64).abs()
+                })
+                .map(|(index, _)| index)
+        })
 }
 
 struct LyricsRecord {
     lyrics: Lyrics,
     path: PathBuf,
+    source: Option<&'static str>,
 }
 
 impl LyricsRecord {
     fn save(&self, state: &mut State) {
+        if state.settings.preview_only {
+            state.unsaved.insert(self.path.clone(), self.lyrics.clone());
+            return;
+        }
+        self.write(state);
+    }
+    fn write(&self, state: &mut State) {
         let path = self.path.clone();
         let lyrics = self.lyrics.clone();
         let sema = state.file_limiter.clone();
-        state.write_joins.spawn(async move {
+        let options = state.settings.write_options();
+        state.writes_total += 1;
+        let hook_failed = state.hook_failed.clone();
+        state.lyric_writes.spawn(async move {
             let lock = sema.acquire_owned().await.unwrap();
-            lyrics.to_file(&path).await?;
+            let written = lyrics.write(&path, &options).await?;
             drop(lock);
-            Ok(())
+            if written > 0
+                && let Err(err) = lyrics.notify_players(&path, &options).await
+            {
+                let _ = hook_failed.send((path, err.to_string()));
+            }
+            Ok(written)
         });
     }
 }
 
-impl<'a> Default for State {
+impl Default for State {
     fn default() -> Self {
-        return State {
+        let (hook_failed, hook_failures) = tokio::sync::mpsc::unbounded_channel();
+        State {
             screen: Screens::Main,
             will_quit: false,
+            quit_warned: false,
             table_state: TableState::default().with_selected(Some(0)),
             music: Vec::default(),
+            scanned_at: SystemTime::UNIX_EPOCH,
+            rescan_joins: JoinSet::new(),
             lyrics: HashMap::default(),
+            sources: HashMap::new(),
+            outcomes: HashMap::new(),
+            unsaved: HashMap::new(),
+            preview_cache: LruCache::new(PREVIEW_CACHE_SIZE),
+            preview_joins: tokio::task::JoinSet::new(),
+            preview_pending: HashSet::new(),
+            preview_failed: HashMap::new(),
             total: 0,
             done: 0,
+            completions: VecDeque::new(),
             api_joins: tokio::task::JoinSet::new(),
+            fetch_queue: VecDeque::new(),
+            queued_at: None,
+            bypass_cache: HashSet::new(),
+            fetch_tasks: HashMap::new(),
+            queue_state: ListState::default(),
+            resumable: Vec::new(),
+            review: Review::default(),
+            queue_saved: (0, 0),
+            queue_saved_at: Instant::now(),
+            failures: Vec::new(),
+            failures_state: ListState::default(),
+            retried: HashSet::new(),
+            retrying: false,
             write_joins: tokio::task::JoinSet::new(),
-            client: reqwest::ClientBuilder::new()
-                .user_agent("LRCFETCH v0.0.0 (https://github.com/hagaraShin/lrcfetch-tui)")
-                .build()
-                .unwrap(),
-            client_limiter: Arc::new(Semaphore::new(50)),
+            lyric_writes: JoinSet::new(),
+            hook_failed,
+            hook_failures,
+            writes_total: 0,
+            writes_done: 0,
+            client: build_client(&Settings::default()),
+            client_limiter: Arc::new(Semaphore::new(Settings::default().concurrent_queries)),
+            pause: watch::Sender::new(false),
+            lrclib_throttle: Arc::new(Throttle::new(Politeness::for_endpoint(LRCLIB_URL))),
+            providers: Arc::new(Registry::default()),
+            limiter_panel: false,
+            marked: HashSet::new(),
+            recording: None,
+            recorded_macro: Vec::new(),
+            candidates: Vec::new(),
+            candidates_for: None,
+            candidates_state: TableState::default(),
+            candidates_status: None,
+            search_joins: JoinSet::new(),
+            search_cache: LruCache::new(SEARCH_CACHE_SIZE),
+            trash_joins: JoinSet::new(),
+            refetch_queue: VecDeque::new(),
+            releases: HashMap::new(),
+            variant_preferences: HashMap::new(),
+            duration_overrides: HashMap::new(),
+            flags: HashMap::new(),
+            misses: Misses::default(),
+            duration_joins: JoinSet::new(),
+            details_status: None,
+            album_apply: None,
+            whats_new_scroll: 0,
+            lyrics_scroll: HashMap::new(),
             file_limiter: Arc::new(Semaphore::new(50)),
+            local_limiter: Arc::new(Semaphore::new(Settings::default().concurrent_local_reads)),
             settings: Settings::default(),
             filter: Filter::default(),
+            applied_filter: Filter::default(),
+            filtered: Vec::new(),
+            sort_added: false,
+            theme_override: None,
             field: None,
             current_string: String::new(),
             filters_popup_state: ListState::default(),
-        };
+            stats: Stats::default(),
+            batch: Stats::default(),
+            batch_tracks: HashSet::new(),
+            debug: DebugInfo::default(),
+            announcements: VecDeque::new(),
+            activity: VecDeque::new(),
+            activity_state: ListState::default(),
+            folders: Vec::new(),
+            folders_state: ListState::default(),
+            loaded_folders: HashSet::new(),
+            folder_joins: JoinSet::new(),
+            index: Arc::default(),
+            scan_found: None,
+            scan_progress: Arc::default(),
+            fetch_after_load: HashSet::new(),
+            announced_selection: None,
+            announced_screen: Screens::Main,
+            cursor: None,
+        }
     }
 }
 
-#[derive(Hash, Clone, Copy, PartialEq, Eq)]
+#[derive(Deserialize, Hash, Debug, Clone, Copy, PartialEq, Eq)]
 enum Screens {
     Main,
     Filters,
+    Lyrics,
+    Failures,
+    Candidates,
+    Details,
+    AlbumApply,
+    WhatsNew,
+    Activity,
+    Folders,
+    Queue,
+}
+
+impl Screens {
+    fn name(&self) -> &'static str {
+        match self {
+            Screens::Main => "Tracks",
+            Screens::Filters => "Filters",
+            Screens::Lyrics => "Lyrics",
+            Screens::Failures => "Failures",
+            Screens::Candidates => "Candidates",
+            Screens::Details => "Details",
+            Screens::AlbumApply => "Apply release",
+            Screens::WhatsNew => "What's new",
+            Screens::Activity => "Activity",
+            Screens::Folders => "Folders",
+            Screens::Queue => "Queue",
+        }
+    }
 }
 
 #[derive(Hash, PartialEq, Eq)]
@@ -438,7 +2590,7 @@ struct KeyBind {
     keycode: KeyCode,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 enum Func {
     ScanAll,
     ScanSelected,
@@ -453,166 +2605,859 @@ enum Func {
     FiltersSelectNext,
     FiltersSelectPrevious,
     OpenSelectedFilter,
+    ToggleLayout,
+    OpenLyricsPopup,
+    CloseLyricsPopup,
+    ScrollLyricsDown,
+    ScrollLyricsUp,
+    ToggleDebugOverlay,
+    OpenFailuresPopup,
+    CloseFailuresPopup,
+    FailuresSelectNext,
+    FailuresSelectPrevious,
+    ToggleLimiterPanel,
+    ToggleMark,
+    ToggleMacroRecording,
+    ReplayMacro,
+    OpenCandidates,
+    CloseCandidates,
+    CandidatesSelectNext,
+    CandidatesSelectPrevious,
+    AcceptCandidate,
+    RejectCandidates,
+    OpenDetails,
+    CloseDetails,
+    EditDuration,
+    RecomputeDuration,
+    ConfirmAlbumApply,
+    CancelAlbumApply,
+    CycleLyricsOutput,
+    CancelQueued,
+    CancelFetches,
+    TogglePause,
+    ExtractEmbedded,
+    ExportMissingPlaylist,
+    TrashAndRefetch,
+    RetryFailed,
+    ResumeQueue,
+    ReviewNext,
+    ToggleSortAdded,
+    Rescan,
+    OpenFolders,
+    CloseFolders,
+    FoldersSelectNext,
+    FoldersSelectPrevious,
+    ShowFolder,
+    ScanFolder,
+    OpenQueue,
+    CloseQueue,
+    QueueSelectNext,
+    QueueSelectPrevious,
+    RemoveFromQueue,
+    CycleFlag,
+    PasteLyrics,
+    SaveSelected,
+    SaveAll,
+    ExportUnsaved,
+    OpenActivity,
+    CloseActivity,
+    ActivitySelectNext,
+    ActivitySelectPrevious,
+    JumpToActivity,
+    OpenWhatsNew,
+    CloseWhatsNew,
+    DismissWhatsNew,
+    WhatsNewScrollDown,
+    WhatsNewScrollUp,
 }
 
-fn default_config_path() -> Option<PathBuf> {
-    if let Ok(xdg_config_home) = env::var("XDG_CONFIG_HOME") {
-        if let Ok(mut path) = absolute(xdg_config_home) {
-            path.push("lrcfetch");
-            path.push("config.ron");
-            if path.exists() {
-                return Some(path);
+impl Func {
+    fn call(&self, state: &mut State) {
+        match self {
+            Func::ScanAll => Self::scan_all(state),
+            Func::ScanSelected => Self::scan_song(state),
+            Func::SelectNext => Self::select_next(state),
+            Func::SelectPrevious => Self::select_previous(state),
+            Func::Quit => Self::quit(state),
+            Func::OpenFilterTitle => {
+                state.current_string = state.filter.title.clone().unwrap_or_default();
+                state.field = Some(Fields::Title);
+            }
+            Func::OpenFilterAlbum => {
+                state.current_string = state.filter.album.clone().unwrap_or_default();
+                state.field = Some(Fields::Album);
+            }
+            Func::OpenFilterArtist => {
+                state.current_string = state.filter.artist.clone().unwrap_or_default();
+                state.field = Some(Fields::Artist);
+            }
+            Func::OpenFiltersPopup => {
+                state.screen = Screens::Filters;
+            }
+            Func::CloseFiltersPopup => {
+                state.screen = Screens::Main;
+            }
+            Func::ToggleLayout => {
+                state.settings.layout = match state.settings.layout {
+                    LayoutMode::Split => LayoutMode::Compact,
+                    LayoutMode::Compact => LayoutMode::Split,
+                };
+            }
+            Func::OpenFailuresPopup => {
+                state.screen = Screens::Failures;
+            }
+            Func::CloseFailuresPopup => {
+                state.screen = Screens::Main;
+            }
+            Func::RetryFailed => {
+                let failures = std::mem::take(&mut state.failures);
+                if !failures.is_empty() {
+                    state.record(format!("Re-queued {} failed fetches", failures.len()), None);
+                }
+                for failure in failures {
+                    state.bypass_cache.insert(failure.data.path.clone());
+                    Self::scan_music(failure.data, state);
+                }
+            }
+            Func::ResumeQueue => {
+                let loaded = state
+                    .music
+                    .iter()
+                    .map(|data| (data.path.as_path(), data))
+                    .collect::<HashMap<_, _>>();
+                let (music, unmatched): (Vec<_>, Vec<_>) = std::mem::take(&mut state.resumable)
+                    .into_iter()
+                    .partition(|path| loaded.contains_key(path.as_path()));
+                let music = music
+                    .iter()
+                    .map(|path| Arc::clone(loaded[path.as_path()]))
+                    .collect::<Vec<_>>();
+                // Tracks the scan hasn't reached yet stay resumable; once the whole library is
+                // loaded, the rest are gone.
+                if state.scanning() || state.settings.lazy_scan {
+                    state.resumable = unmatched;
+                } else if !unmatched.is_empty() {
+                    state.record(
+                        format!(
+                            "Dropped {} queued tracks no longer in the library",
+                            unmatched.len()
+                        ),
+                        None,
+                    );
+                }
+                if !music.is_empty() {
+                    state.record(format!("Resumed {} queued tracks", music.len()), None);
+                }
+                for data in music {
+                    Self::scan_music(data, state);
+                }
+            }
+            Func::ReviewNext => {
+                let next = state
+                    .review
+                    .paths()
+                    .find_map(|path| state.music.iter().find(|data| &data.path == path).cloned());
+                match next {
+                    Some(data) => Self::open_candidates_for(state, data),
+                    None if state.review.is_empty() => {
+                        state.announce("Nothing to review".to_string())
+                    }
+                    None => state.announce("Tracks to review are not loaded".to_string()),
+                }
+            }
+            Func::ToggleSortAdded => {
+                state.sort_added = !state.sort_added;
+                state.refilter();
+            }
+            Func::Rescan => {
+                state.announce("Rescanning library".to_string());
+                state.rescan();
+            }
+            Func::CycleFlag => {
+                let Some(path) = state.selected_music().map(|data| data.path.clone()) else {
+                    return;
+                };
+                match TrackFlag::cycle(state.flags.get(&path).copied()) {
+                    Some(flag) => state.flags.insert(path, flag),
+                    None => state.flags.remove(&path),
+                };
+                if state.filter.flag.is_some() {
+                    state.refilter();
+                }
+                let flags = state.flags.clone();
+                state
+                    .write_joins
+                    .spawn(async move { store::save(FLAGS_FILE, &flags).await.map(|()| 0) });
+            }
+            Func::PasteLyrics => {
+                if state.selected_music().is_some() {
+                    state.current_string.clear();
+                    state.field = Some(Fields::Paste);
+                }
+            }
+            Func::SaveSelected => {
+                let Some(path) = state.selected_music().map(|data| data.path.clone()) else {
+                    return;
+                };
+                Self::save_unsaved(state, path);
+            }
+            Func::SaveAll => {
+                for path in state.unsaved.keys().cloned().collect::<Vec<_>>() {
+                    Self::save_unsaved(state, path);
+                }
+            }
+            Func::ExportUnsaved => Self::export_unsaved(state),
+            Func::OpenActivity => {
+                state.activity_state.select_first();
+                state.screen = Screens::Activity;
+            }
+            Func::CloseActivity => {
+                state.screen = Screens::Main;
+            }
+            Func::ActivitySelectNext => state.activity_state.select_next(),
+            Func::ActivitySelectPrevious => state.activity_state.select_previous(),
+            Func::JumpToActivity => {
+                let position = state
+                    .activity_state
+                    .selected()
+                    .and_then(|index| state.activity.get(index))
+                    .and_then(|activity| activity.path.as_ref())
+                    .and_then(|path| state.filtered_music().position(|data| data.path == *path));
+                if let Some(position) = position {
+                    state.table_state.select(Some(position));
+                    state.screen = Screens::Main;
+                }
+            }
+            Func::OpenFolders => {
+                state.folders = state.folders();
+                state.folders_state.select_first();
+                state.screen = Screens::Folders;
+            }
+            Func::CloseFolders => {
+                state.screen = Screens::Main;
+            }
+            Func::FoldersSelectNext => state.folders_state.select_next(),
+            Func::FoldersSelectPrevious => state.folders_state.select_previous(),
+            Func::ShowFolder => {
+                if let Some(folder) = state.selected_folder() {
+                    state.load_folder(folder.clone());
+                    state.filter.folder = Some(folder);
+                    state.update_filter();
+                    state.table_state.select_first();
+                    state.screen = Screens::Main;
+                }
+            }
+            Func::ScanFolder => {
+                if let Some(folder) = state.selected_folder() {
+                    if state.settings.lazy_scan && !state.folder_loaded(&folder) {
+                        state.load_folder(folder.clone());
+                        state.fetch_after_load.insert(folder);
+                        state.screen = Screens::Main;
+                        return;
+                    }
+                    let music = state
+                        .music
+                        .iter()
+                        .filter(|data| data.path.starts_with(&folder))
+                        .cloned()
+                        .collect();
+                    Self::scan_missing(state, music);
+                    state.screen = Screens::Main;
+                }
+            }
+            Func::FailuresSelectNext => state.failures_state.select_next(),
+            Func::FailuresSelectPrevious => state.failures_state.select_previous(),
+            Func::ToggleMark => {
+                if let Some(path) = state.selected_music().map(|data| data.path.clone())
+                    && !state.marked.remove(&path)
+                {
+                    state.marked.insert(path);
+                }
+            }
+            Func::ToggleMacroRecording => match state.recording.take() {
+                Some(actions) => state.recorded_macro = actions,
+                None => state.recording = Some(Vec::new()),
+            },
+            Func::ReplayMacro => Self::replay_macro(state),
+            Func::OpenCandidates => Self::open_candidates(state),
+            Func::CloseCandidates => {
+                state.screen = Screens::Main;
+            }
+            Func::CandidatesSelectNext => state.candidates_state.select_next(),
+            Func::CandidatesSelectPrevious => state.candidates_state.select_previous(),
+            Func::AcceptCandidate => Self::accept_candidate(state),
+            Func::RejectCandidates => Self::reject_candidates(state),
+            Func::ConfirmAlbumApply => {
+                if let Some(apply) = state.album_apply.take() {
+                    for data in apply.tracks {
+                        let mut data = (*data).clone();
+                        data.album = apply.album.clone();
+                        Self::scan_music(Arc::new(data), state);
+                    }
+                }
+                state.screen = Screens::Main;
+            }
+            Func::CancelQueued => {
+                let Some(path) = state.selected_music().map(|data| data.path.clone()) else {
+                    return;
+                };
+                let before = state.fetch_queue.len();
+                state.fetch_queue.retain(|data| data.path != path);
+                state.queued_at = None;
+                state.total -= before - state.fetch_queue.len();
+            }
+            Func::CancelFetches => {
+                let cancelled = state.fetch_queue.len() + state.api_joins.len();
+                if cancelled == 0 {
+                    return;
+                }
+                // Dropping the set aborts every request still in flight.
+                state.api_joins = JoinSet::new();
+                state.fetch_tasks.clear();
+                state.fetch_queue.clear();
+                state.queued_at = None;
+                state.total = 0;
+                state.done = 0;
+                state.completions.clear();
+                state.batch = Stats::default();
+                state.batch_tracks.clear();
+                state.retrying = false;
+                state.retried.clear();
+                state.record(format!("Cancelled {cancelled} fetches"), None);
+                state.announce(format!("Cancelled {cancelled} fetches"));
+            }
+            Func::TogglePause => {
+                let paused = *state.pause.borrow();
+                let message = if state.pause.send_replace(!paused) {
+                    "Resumed fetching"
+                } else {
+                    "Paused fetching"
+                };
+                state.record(message.to_string(), None);
+                state.announce(message.to_string());
+            }
+            Func::ScrollLyricsDown => state.scroll_lyrics(true),
+            Func::ScrollLyricsUp => state.scroll_lyrics(false),
+            Func::OpenQueue => {
+                state.queue_state.select_first();
+                state.screen = Screens::Queue;
+            }
+            Func::CloseQueue => {
+                state.screen = Screens::Main;
+            }
+            Func::QueueSelectNext => state.queue_state.select_next(),
+            Func::QueueSelectPrevious => state.queue_state.select_previous(),
+            Func::RemoveFromQueue => {
+                let Some(selected) = state.queue_state.selected() else {
+                    return;
+                };
+                let running = state
+                    .running()
+                    .into_iter()
+                    .map(|(id, _)| id)
+                    .collect::<Vec<_>>();
+                let data = match running.get(selected) {
+                    Some(id) => {
+                        // Forgotten first, so the aborted task is not reported as a failure.
+                        let Some(fetch) = state.fetch_tasks.remove(id) else {
+                            return;
+                        };
+                        fetch.abort.abort();
+                        fetch.data
+                    }
+                    None => {
+                        let Some(data) = state.fetch_queue.remove(selected - running.len()) else {
+                            return;
+                        };
+                        state.queued_at = None;
+                        state.total -= 1;
+                        data
+                    }
+                };
+                state.record(
+                    format!("Removed {} from the fetch queue", data.title),
+                    Some(data.path.clone()),
+                );
+                state.announce(format!("Removed {}", data.title));
             }
-        };
-    }
-    if let Some(mut home) = home_dir() {
-        home.push(".config");
-        home.push("lrcfetch");
-        home.push("config.ron");
-        if home.exists() {
-            return Some(home);
-        };
-    }
-    if let Ok(mut pwd) = current_dir() {
-        pwd.push("config.ron");
-        if pwd.exists() {
-            return Some(pwd);
-        };
-    }
-    None
-}
-
-fn default_future_config_path() -> Option<PathBuf> {
-    if let Ok(xdg_config_home) = env::var("XDG_CONFIG_HOME") {
-        if let Ok(mut path) = absolute(xdg_config_home) {
-            path.push("lrcfetch");
-            path.push("config.ron");
-            return Some(path);
-        };
-    }
-    if let Some(mut home) = home_dir() {
-        home.push(".config");
-        home.push("lrcfetch");
-        home.push("config.ron");
-        return Some(home);
-    }
-    if let Ok(mut pwd) = current_dir() {
-        pwd.push("config.ron");
-        return Some(pwd);
-    }
-    None
-}
-
-impl Func {
-    fn call(&self, state: &mut State) {
-        match self {
-            Func::ScanAll => Self::scan_all(state),
-            Func::ScanSelected => Self::scan_song(state),
-            Func::SelectNext => Self::select_next(state),
-            Func::SelectPrevious => Self::select_previous(state),
-            Func::Quit => Self::quit(state),
-            Func::OpenFilterTitle => {
-                state.current_string = state.filter.title.clone().unwrap_or(String::new());
-                state.field = Some(Fields::Title);
+            Func::ExtractEmbedded => {
+                let music = state.filtered_music().cloned().collect::<Vec<_>>();
+                for data in music {
+                    if data.embedded.sidecar(&data.path).is_none() {
+                        continue;
+                    }
+                    let semaphore = state.file_limiter.clone();
+                    state.writes_total += 1;
+                    state.lyric_writes.spawn(async move {
+                        let _lock = semaphore.acquire_owned().await;
+                        data.extract_embedded(false).await
+                    });
+                }
             }
-            Func::OpenFilterAlbum => {
-                state.current_string = state.filter.album.clone().unwrap_or(String::new());
-                state.field = Some(Fields::Album);
+            Func::ExportMissingPlaylist => {
+                let missing = state
+                    .filtered_music()
+                    .filter(|data| {
+                        matches!(
+                            state.lyrics.get(&data.path),
+                            None | Some(LyricsStatus::None)
+                        )
+                    })
+                    .map(|data| &**data);
+                let playlist = to_m3u(missing);
+                let path = state.settings.music_path.join(MISSING_PLAYLIST);
+                state.announce(format!("Writing {}", path.display()));
+                state.record(format!("Wrote {}", path.display()), None);
+                state.write_joins.spawn(async move {
+                    tokio::fs::write(path, &playlist).await?;
+                    Ok(playlist.len())
+                });
             }
-            Func::OpenFilterArtist => {
-                state.current_string = state.filter.artist.clone().unwrap_or(String::new());
-                state.field = Some(Fields::Artist);
+            Func::TrashAndRefetch => Self::trash_and_refetch(state),
+            Func::OpenWhatsNew => {
+                state.whats_new_scroll = 0;
+                state.screen = Screens::WhatsNew;
             }
-            Func::OpenFiltersPopup => {
-                state.screen = Screens::Filters;
+            Func::CloseWhatsNew => {
+                state.screen = Screens::Main;
             }
-            Func::CloseFiltersPopup => {
+            Func::DismissWhatsNew => {
+                state.screen = Screens::Main;
+                let version = Some(env!("CARGO_PKG_VERSION").to_string());
+                state.write_joins.spawn(async move {
+                    store::save(SEEN_VERSION_FILE, &version).await.map(|()| 0)
+                });
+            }
+            Func::WhatsNewScrollDown => {
+                state.whats_new_scroll = state.whats_new_scroll.saturating_add(1);
+            }
+            Func::WhatsNewScrollUp => {
+                state.whats_new_scroll = state.whats_new_scroll.saturating_sub(1);
+            }
+            Func::CycleLyricsOutput => {
+                state.settings.lyrics_output = state.settings.lyrics_output.next();
+            }
+            Func::CancelAlbumApply => {
+                state.album_apply = None;
+                state.screen = Screens::Main;
+            }
+            Func::OpenDetails => {
+                state.details_status = None;
+                state.screen = Screens::Details;
+            }
+            Func::CloseDetails => {
+                state.screen = Screens::Main;
+            }
+            Func::EditDuration => {
+                if let Some(data) = state.selected_music() {
+                    state.current_string = data.duration.to_string();
+                    state.field = Some(Fields::Duration);
+                }
+            }
+            Func::RecomputeDuration if !cfg!(feature = "decode") => {
+                state.details_status = Some("built without the decode feature".to_string());
+            }
+            Func::RecomputeDuration => {
+                if let Some(data) = state.selected_music() {
+                    let path = data.path.clone();
+                    state.details_status = Some("decoding...".to_string());
+                    state.duration_joins.spawn_blocking(move || {
+                        let duration = decode_duration(&path);
+                        (path, duration)
+                    });
+                }
+            }
+            Func::ToggleLimiterPanel => {
+                state.limiter_panel = !state.limiter_panel;
+            }
+            Func::ToggleDebugOverlay => {
+                state.debug.visible = !state.debug.visible;
+            }
+            Func::OpenLyricsPopup => {
+                state.screen = Screens::Lyrics;
+            }
+            Func::CloseLyricsPopup => {
                 state.screen = Screens::Main;
             }
             Func::FiltersSelectNext => state.filters_popup_state.select_next(),
             Func::FiltersSelectPrevious => state.filters_popup_state.select_previous(),
             Func::OpenSelectedFilter => match state.filters_popup_state.selected() {
                 Some(0) => {
-                    state.current_string = state.filter.title.clone().unwrap_or(String::new());
+                    state.current_string = state.filter.title.clone().unwrap_or_default();
                     state.field = Some(Fields::Title);
                 }
                 Some(1) => {
-                    state.current_string = state.filter.artist.clone().unwrap_or(String::new());
+                    state.current_string = state.filter.artist.clone().unwrap_or_default();
                     state.field = Some(Fields::Artist);
                 }
                 Some(2) => {
-                    state.current_string = state.filter.album.clone().unwrap_or(String::new());
+                    state.current_string = state.filter.album.clone().unwrap_or_default();
                     state.field = Some(Fields::Album);
                 }
+                Some(3) => {
+                    state.filter.include_short = !state.filter.include_short;
+                    state.update_filter();
+                }
+                Some(4) => {
+                    state.filter.flag = TrackFlag::cycle(state.filter.flag);
+                    state.update_filter();
+                }
+                Some(5) => {
+                    state.current_string = state
+                        .filter
+                        .added_within_days
+                        .map(|days| days.to_string())
+                        .unwrap_or_default();
+                    state.field = Some(Fields::Added);
+                }
+                Some(6) => {
+                    state.filter.folder = None;
+                    state.update_filter();
+                }
                 _ => {}
             },
         }
     }
 
-    fn set_concurrent_queries(state: &mut State, value: usize) {
-        state.client_limiter.forget_permits(usize::MAX);
-        state.client_limiter.add_permits(value);
-        state.settings.concurrent_queries = value;
+    fn recordable(&self) -> bool {
+        !matches!(
+            self,
+            Func::ToggleMacroRecording | Func::ReplayMacro | Func::Quit
+        )
     }
-    async fn set_settings(state: &mut State, settings: Settings) {
-        state.settings = settings;
-        Func::set_concurrent_queries(state, state.settings.concurrent_queries);
-        let Some(data) = scan_music(state.settings.music_path.clone()) else {
+
+    fn replay_macro(state: &mut State) {
+        if state.recorded_macro.is_empty() {
+            return;
+        }
+        let actions = state.recorded_macro.clone();
+        let targets = state
+            .filtered_music()
+            .filter(|data| state.marked.contains(&data.path))
+            .map(|data| data.path.clone())
+            .collect::<Vec<_>>();
+        for path in targets {
+            let Some(position) = state.filtered_music().position(|data| data.path == path) else {
+                continue;
+            };
+            state.table_state.select(Some(position));
+            for action in &actions {
+                action.call(state);
+            }
+        }
+    }
+
+    /// Backs up and deletes the marked sidecars that fail the `verify` checks, then walks
+    /// each of those tracks through the candidate picker.
+    fn trash_and_refetch(state: &mut State) {
+        let marked = state
+            .filtered_music()
+            .filter(|data| state.marked.contains(&data.path))
+            .cloned()
+            .collect::<Vec<_>>();
+        for data in marked {
+            let Some(&status) = state.lyrics.get(&data.path) else {
+                continue;
+            };
+            let Some(sidecar) = status.sidecar(&data.path) else {
+                continue;
+            };
+            let semaphore = state.file_limiter.clone();
+            state.trash_joins.spawn(async move {
+                let _lock = semaphore.acquire_owned().await;
+                let lyrics = tokio::fs::read_to_string(&sidecar).await.ok()?;
+                let lyrics = match status {
+                    LyricsStatus::Synced => Lyrics::Synced(lyrics),
+                    _ => Lyrics::Plain(lyrics),
+                };
+                if cli::sidecar_problems(&data, &lyrics, None).is_empty() {
+                    return None;
+                }
+                let mut backup = sidecar.clone().into_os_string();
+                backup.push(".bak");
+                tokio::fs::copy(&sidecar, backup).await.ok()?;
+                tokio::fs::remove_file(&sidecar).await.ok()?;
+                Some(data)
+            });
+        }
+    }
+
+    /// Writes the unsaved lyrics to `UNSAVED_EXPORT` in the music directory without touching
+    /// the tracks, to keep or `import` later.
+    fn export_unsaved(state: &mut State) {
+        if state.unsaved.is_empty() {
+            state.announce("No unsaved lyrics".to_string());
+            return;
+        }
+        let entries = state
+            .music
+            .iter()
+            .filter_map(|data| {
+                let lyrics = state.unsaved.get(&data.path)?;
+                Some(LibraryEntry::new(
+                    data,
+                    lyrics,
+                    state.flags.get(&data.path).copied(),
+                ))
+            })
+            .collect::<Vec<_>>();
+        let path = state.settings.music_path.join(UNSAVED_EXPORT);
+        state.announce(format!("Writing {}", path.display()));
+        state.record(
+            format!(
+                "Exported {} unsaved lyrics to {}",
+                entries.len(),
+                path.display()
+            ),
+            None,
+        );
+        state.write_joins.spawn(async move {
+            let json = serde_json::to_string_pretty(&entries).map_err(tokio::io::Error::other)?;
+            tokio::fs::write(path, &json).await?;
+            Ok(json.len())
+        });
+    }
+
+    fn save_unsaved(state: &mut State, path: PathBuf) {
+        let Some(lyrics) = state.unsaved.remove(&path) else {
+            return;
+        };
+        let title = state
+            .music
+            .iter()
+            .find(|data| data.path == path)
+            .map_or(String::new(), |data| data.title.to_string());
+        state.record(
+            format!("Saved previewed lyrics for {title}"),
+            Some(path.clone()),
+        );
+        LyricsRecord {
+            lyrics,
+            path,
+            source: None,
+        }
+        .write(state);
+    }
+
+    fn open_candidates(state: &mut State) {
+        let Some(data) = state.selected_music().cloned() else {
             return;
         };
-        state.music = data;
-        let mut joinset = JoinSet::new();
-        for music in state.music.iter() {
-            let path = music.path.clone();
-            let music = music.clone();
-            joinset.spawn(async move { (path, music.check_lyrics().await) });
+        Self::open_candidates_for(state, data);
+    }
+
+    fn open_candidates_for(state: &mut State, data: Arc<MusicData>) {
+        state.candidates.clear();
+        state.candidates_state.select(None);
+        state.candidates_status = Some("searching...".to_string());
+        state.candidates_for = Some(Arc::clone(&data));
+        state.screen = Screens::Candidates;
+        let reviewed = state.review.get(&data.path).map(|scored| {
+            scored
+                .iter()
+                .map(|scored| scored.candidate.clone())
+                .collect::<Vec<_>>()
+        });
+        if let Some(candidates) =
+            reviewed.or_else(|| state.search_cache.get(&search_key(&data)).cloned())
+        {
+            state.show_candidates(&data, candidates);
+            return;
         }
-        while let Some(result) = joinset.join_next().await {
-            if let Ok((path, Ok(lyrics))) = result {
-                state.lyrics.insert(path, lyrics);
+        let client = state.client.clone();
+        let throttle = state.lrclib_throttle.clone();
+        let lrclib_url = state.settings.lrclib_url.clone();
+        let registry = state.providers.clone();
+        let providers = state.settings.providers_for(&data).to_vec();
+        state.search_joins.spawn(async move {
+            let candidates = data.search(&client, &throttle, &lrclib_url).await;
+            let (variants, complete) = registry.variants(&providers, &data).await;
+            match candidates {
+                Ok(mut candidates) => {
+                    candidates.extend(variants);
+                    (data, Ok(candidates), complete)
+                }
+                Err(_) if !variants.is_empty() => (data, Ok(variants), false),
+                Err(err) => (data, Err(err), false),
             }
+        });
+    }
+
+    /// Drops the track from the review queue without saving anything, counting it as a miss.
+    fn reject_candidates(state: &mut State) {
+        let Some(data) = state.candidates_for.clone() else {
+            return;
+        };
+        state.screen = Screens::Main;
+        if !state.review.remove(&data.path) {
+            return;
+        }
+        state.save_review();
+        state.misses.record(&data.path, &Lyrics::None);
+        state
+            .outcomes
+            .insert(data.path.clone(), FetchOutcome::NotFound);
+        state.record(
+            format!("Rejected the search matches for {}", data.title),
+            Some(data.path.clone()),
+        );
+    }
+
+    fn accept_candidate(state: &mut State) {
+        let (Some(data), Some(selected)) = (
+            state.candidates_for.clone(),
+            state.candidates_state.selected(),
+        ) else {
+            return;
+        };
+        let Some(candidate) = state.candidates.get(selected).cloned() else {
+            return;
+        };
+        if let Some(reason) = candidate
+            .lyrics()
+            .implausible(&state.settings.write_options())
+        {
+            state.announce(format!("Refused: {reason}"));
+            state.candidates_status = Some(format!("refused: {reason}"));
+            return;
+        }
+        let source = candidate.source.unwrap_or("lrclib");
+        let record = LyricsRecord {
+            lyrics: candidate.lyrics(),
+            path: data.path.clone(),
+            source: Some(source),
+        };
+        state.sources.insert(record.path.clone(), source);
+        let label = candidate
+            .variant
+            .map_or(String::new(), |variant| format!(" {variant}"));
+        state.record(
+            format!("Wrote{label} lyrics from {source} for {}", data.title),
+            Some(data.path.clone()),
+        );
+        if let Some(variant) = candidate.variant {
+            state
+                .variant_preferences
+                .insert(source.to_string(), variant.to_string());
+            let preferences = state.variant_preferences.clone();
+            state
+                .write_joins
+                .spawn(async move { store::save(VARIANTS_FILE, &preferences).await.map(|()| 0) });
+        }
+        if state.review.remove(&data.path) {
+            state.save_review();
+        }
+        record.save(state);
+        state.stats.record_lyrics(&record.lyrics);
+        state
+            .lyrics
+            .insert(record.path.clone(), record.lyrics.status());
+        state.preview_cache.put(record.path, record.lyrics);
+        // Variants from other providers carry no release, so there's no album to remember.
+        if candidate.source.is_some() || candidate.album_name.is_empty() {
+            state.screen = Screens::Main;
+            return;
+        }
+        state
+            .releases
+            .insert(release_key(&data), candidate.album_name.clone());
+        let releases = state.releases.clone();
+        state
+            .write_joins
+            .spawn(async move { store::save(RELEASES_FILE, &releases).await.map(|()| 0) });
+        let key = release_key(&data);
+        let tracks = state
+            .music
+            .iter()
+            .filter(|other| other.path != data.path && release_key(other) == key)
+            .filter(|other| {
+                matches!(
+                    state.lyrics.get(&other.path),
+                    None | Some(LyricsStatus::None | LyricsStatus::Plain)
+                )
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if tracks.is_empty() {
+            state.screen = Screens::Main;
+        } else {
+            state.album_apply = Some(AlbumApply {
+                album: Arc::from(candidate.album_name.as_str()),
+                tracks,
+            });
+            state.screen = Screens::AlbumApply;
+        }
+    }
+
+    async fn set_settings(state: &mut State, settings: Settings) {
+        let queries = state.settings.concurrent_queries;
+        let local_reads = state.settings.concurrent_local_reads;
+        state.settings = settings;
+        if let Some(theme) = state.theme_override {
+            state.settings.theme = theme;
         }
+        state.client = build_client(&state.settings);
+        state.lrclib_throttle = Arc::new(Throttle::new(
+            state
+                .settings
+                .politeness_for("lrclib", &state.settings.lrclib_url),
+        ));
+        state.providers = Arc::new(Registry::new(
+            &state.settings,
+            &state.client,
+            state.lrclib_throttle.clone(),
+            ResponseCache::for_settings(&state.settings).await,
+        ));
+        let settings = &state.settings;
+        resize_limiter(&state.client_limiter, queries, settings.concurrent_queries);
+        resize_limiter(
+            &state.local_limiter,
+            local_reads,
+            settings.concurrent_local_reads,
+        );
+        state.filter.min_duration = state.settings.min_duration;
+        state.refilter();
+        state.scan();
     }
+    /// Queues the selected track even while its miss cools down, and without a cached response.
     fn scan_song(state: &mut State) {
-        let Some(selected) = state.table_state.selected() else {
+        let Some(m) = state.selected_music().cloned() else {
             return;
         };
-        let m = state.music[selected].clone();
+        state.bypass_cache.insert(m.path.clone());
         Self::scan_music(m, state);
         Self::select_next(state);
     }
     fn scan_all(state: &mut State) {
-        for m in state
-            .music
-            .clone()
-            .into_iter()
-            .filter(|x| state.filter.apply(x))
-            .collect::<Vec<_>>()
-        {
-            if let Some(Lyrics::None) = state.lyrics.get(&m.path) {
-                Self::scan_music(m, state);
-            } else if let Some(Lyrics::Plain(_)) = state.lyrics.get(&m.path) {
+        let music = state.filtered_music().cloned().collect();
+        Self::scan_missing(state, music);
+    }
+    /// Queues the tracks in `music` that lack synced lyrics, minus recent misses.
+    fn scan_missing(state: &mut State, music: Vec<Arc<MusicData>>) {
+        let cooldown = state.settings.miss_cooldown_hours;
+        let mut skipped = 0;
+        for m in music {
+            if let Some(LyricsStatus::None | LyricsStatus::Plain) = state.lyrics.get(&m.path) {
+                if state.misses.cooling_down(&m.path, cooldown) {
+                    skipped += 1;
+                    continue;
+                }
                 Self::scan_music(m, state);
             }
         }
+        if skipped > 0 {
+            state.record(
+                format!(
+                    "Skipped {skipped} tracks that missed in the last {cooldown}h; s asks again for the selected one"
+                ),
+                None,
+            );
+        }
     }
-    fn scan_music(data: MusicData, state: &mut State) {
-        let client = state.client.clone();
-        let semaphore = state.client_limiter.clone();
-        state.api_joins.spawn(async move {
-            let Ok(lock) = semaphore.acquire_owned().await else {
-                return LyricsRecord {
-                    lyrics: Lyrics::None,
-                    path: data.path,
-                };
-            };
-            let lyrics = data.query(&client).await;
-            drop(lock);
-            LyricsRecord {
-                lyrics,
-                path: data.path,
-            }
-        });
+    fn scan_music(data: Arc<MusicData>, state: &mut State) {
+        state.batch_tracks.insert(data.path.clone());
+        state.fetch_queue.push_back(data);
+        state.queued_at = None;
         state.total += 1;
     }
     fn select_next(state: &mut State) {
@@ -623,141 +3468,496 @@ impl Func {
         state.table_state.select_previous();
     }
 
+    /// Quits, unless that would lose unsaved lyrics nobody was warned about yet.
     fn quit(state: &mut State) {
+        if !state.unsaved.is_empty() && !state.quit_warned {
+            state.quit_warned = true;
+            let message = format!(
+                "{} lyrics are unsaved; W writes them, U exports them, q again quits",
+                state.unsaved.len()
+            );
+            state.announce(message.clone());
+            state.record(message, None);
+            return;
+        }
         state.will_quit = true;
     }
 }
 
-async fn get_or_create_config(state: &mut State) {
-    if let Some(config_path) = default_config_path() {
-        let config_file = tokio::fs::read_to_string(config_path)
-            .await
-            .unwrap_or_default();
-        if let Ok(settings) = ron::from_str::<Settings>(config_file.as_str()) {
-            Func::set_settings(state, settings).await;
-        }
-    } else if let Some(path) = default_future_config_path() {
-        Func::set_settings(state, Settings::default()).await;
-        if let Some(parent) = path.parent() {
-            let Ok(()) = tokio::fs::create_dir_all(parent).await else {
-                return;
-            };
-        } else {
-            return;
-        };
-        let Ok(mut file) = tokio::fs::OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(path)
-            .await
-        else {
-            return;
-        };
-        let settings = Settings::default();
-        let ron = ron::to_string(&settings).unwrap();
-        file.write_all(ron.as_bytes()).await.unwrap();
+/// Moves `limiter` from `from` permits to `to`. Permits held by running tasks are taken
+/// back as they are released, so the limit never ends up above `to`.
+fn resize_limiter(limiter: &Arc<Semaphore>, from: usize, to: usize) {
+    if to >= from {
+        limiter.add_permits(to - from);
+        return;
+    }
+    let surplus = from - to;
+    let forgotten = limiter.forget_permits(surplus);
+    if forgotten < surplus {
+        let limiter = limiter.clone();
+        tokio::spawn(async move {
+            if let Ok(permits) = limiter
+                .acquire_many_owned((surplus - forgotten) as u32)
+                .await
+            {
+                permits.forget();
+            }
+        });
     }
 }
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if cli.version {
+        return cli::version(cli.verbose);
+    }
+    if cli.man {
+        return cli::man();
+    }
+    match cli.command {
+        None => run_tui(cli.dir).await,
+        Some(command) => cli::run(command).await,
+    }
+}
+
+async fn run_tui(dir: Option<PathBuf>) -> ExitCode {
+    let Some(mut settings) = get_or_create_config().await else {
+        eprintln!("lrcfetch: could not load the configuration file");
+        return ExitCode::FAILURE;
+    };
+    if let Some(dir) = dir {
+        settings.music_path = dir;
+    }
     let mut terminal = ratatui::init();
-    let mut keymap = std::collections::HashMap::<KeyBind, Func>::new();
+    let _ = crossterm::execute!(
+        std::io::stdout(),
+        event::EnableBracketedPaste,
+        event::EnableFocusChange
+    );
     let mut state = State::default();
-    for map in KEYMAP {
-        keymap.insert(map.0, map.1);
+    let preset: Preset = store::load(PRESET_FILE).await;
+    let (keymap, errors) = preset.keymap();
+    for err in errors {
+        state.record(format!("Skipped a preset binding: {err}"), None);
+    }
+    state.theme_override = preset.theme;
+    state.releases = store::load(RELEASES_FILE).await;
+    state.variant_preferences = store::load(VARIANTS_FILE).await;
+    state.duration_overrides = store::load(DURATIONS_FILE).await;
+    state.flags = store::load(FLAGS_FILE).await;
+    state.index = Arc::new(Index::load().await);
+    state.misses = store::load(MISSES_FILE).await;
+    let seen_version: Option<String> = store::load(SEEN_VERSION_FILE).await;
+    if seen_version.as_deref() != Some(env!("CARGO_PKG_VERSION"))
+        && release_notes(env!("CARGO_PKG_VERSION")).is_some()
+    {
+        state.screen = Screens::WhatsNew;
+    }
+    Func::set_settings(&mut state, settings).await;
+    state.resumable = store::load(QUEUE_FILE).await;
+    state.review = store::load(REVIEW_FILE).await;
+    if !state.resumable.is_empty() {
+        state.record(
+            format!(
+                "{} tracks were still queued last session; u resumes them",
+                state.resumable.len()
+            ),
+            None,
+        );
     }
-    let mut args = env::args();
-    if let None = args.next() {};
-    get_or_create_config(&mut state).await;
 
     loop {
-        if state.total == state.done {
+        if state.total == state.done && (state.total == 0 || !state.retry_transient()) {
+            if state.total > 0 {
+                let tracks = std::mem::take(&mut state.batch_tracks);
+                state.announce(format!("Finished fetching {} tracks", tracks.len()));
+                let mut batch = std::mem::take(&mut state.batch);
+                // Only what is still failing after the automatic retries.
+                batch.errors = state
+                    .failures
+                    .iter()
+                    .map(|failure| &failure.data.path)
+                    .filter(|path| tracks.contains(*path))
+                    .collect::<HashSet<_>>()
+                    .len();
+                if state.settings.notify_on_finish {
+                    notify_desktop(format!(
+                        "Fetched {} tracks: {} synced, {} plain, {} not found, {} to review, {} errors",
+                        tracks.len(),
+                        batch.synced,
+                        batch.plain,
+                        batch.not_found,
+                        batch.borderline,
+                        batch.errors
+                    ));
+                }
+                let providers = state.providers.clone();
+                let misses = state.misses.clone();
+                state.write_joins.spawn(async move {
+                    store::save(MISSES_FILE, &misses).await?;
+                    providers.save_cache().await.map(|()| 0)
+                });
+            }
             state.total = 0;
             state.done = 0;
+            state.completions.clear();
+            state.retrying = false;
+            state.retried.clear();
         }
 
-        while let Some(Ok(log)) = state.api_joins.try_join_next() {
-            log.save(&mut state);
-            state.lyrics.insert(log.path, log.lyrics);
+        state.dispatch();
+        let idle = state.fetch_queue.is_empty() && state.api_joins.is_empty();
+        if (state.fetch_queue.len(), state.api_joins.len()) != state.queue_saved
+            && (idle || state.queue_saved_at.elapsed() >= QUEUE_SAVE_INTERVAL)
+        {
+            state.save_queue();
+        }
+        while let Some(result) = state.api_joins.try_join_next_with_id() {
             state.done += 1;
+            state.completions.push_back(Instant::now());
+            while state
+                .completions
+                .front()
+                .is_some_and(|at| at.elapsed() > RATE_WINDOW)
+            {
+                state.completions.pop_front();
+            }
+            match result {
+                Ok((id, Ok((log, review)))) => {
+                    state.fetch_tasks.remove(&id);
+                    state
+                        .failures
+                        .retain(|failure| failure.data.path != log.path);
+                    log.save(&mut state);
+                    match log.source {
+                        Some(source) => state.sources.insert(log.path.clone(), source),
+                        None => state.sources.remove(&log.path),
+                    };
+                    if let Some(data) = state.music.iter().find(|data| data.path == log.path) {
+                        let (message, activity) = match review.first() {
+                            Some(best) => (
+                                format!("{}: needs review", data.title),
+                                format!(
+                                    "Kept {} matches for {} to review (best scored {:.2})",
+                                    review.len(),
+                                    data.title,
+                                    best.score
+                                ),
+                            ),
+                            None => {
+                                let source = log
+                                    .source
+                                    .map_or(String::new(), |name| format!(" from {name}"));
+                                (
+                                    format!("{}: {}", data.title, log.lyrics.status()),
+                                    format!(
+                                        "Fetched {} lyrics for {}{source}",
+                                        log.lyrics.status(),
+                                        data.title
+                                    ),
+                                )
+                            }
+                        };
+                        state.announce(message);
+                        state.record(activity, Some(log.path.clone()));
+                    }
+                    if review.is_empty() {
+                        state.stats.record_lyrics(&log.lyrics);
+                        state.batch.record_lyrics(&log.lyrics);
+                        state.misses.record(&log.path, &log.lyrics);
+                    } else {
+                        state.stats.record_borderline();
+                        state.batch.record_borderline();
+                    }
+                    let outcome = match log.lyrics.status() {
+                        LyricsStatus::None if !review.is_empty() => FetchOutcome::NeedsReview,
+                        LyricsStatus::None => FetchOutcome::NotFound,
+                        status => FetchOutcome::Found(status),
+                    };
+                    if !review.is_empty() {
+                        state.review.add(log.path.clone(), review);
+                        state.save_review();
+                    } else if state.review.remove(&log.path) {
+                        state.save_review();
+                    }
+                    state.outcomes.insert(log.path.clone(), outcome);
+                    state.lyrics.insert(log.path.clone(), log.lyrics.status());
+                    state.preview_cache.put(log.path, log.lyrics);
+                }
+                Ok((id, Err(error))) => {
+                    state.stats.errors += 1;
+                    let Some(InFlight { data, .. }) = state.fetch_tasks.remove(&id) else {
+                        continue;
+                    };
+                    state.announce(format!("{} failed: {}", data.title, error.reason));
+                    state.record(
+                        format!("Fetch failed for {}: {}", data.title, error.reason),
+                        Some(data.path.clone()),
+                    );
+                    state
+                        .outcomes
+                        .insert(data.path.clone(), FetchOutcome::Error(error.reason.clone()));
+                    state.failures.push(Failure {
+                        data,
+                        reason: error.reason,
+                        transient: error.transient,
+                    });
+                }
+                Err(err) => {
+                    let Some(InFlight { data, .. }) = state.fetch_tasks.remove(&err.id()) else {
+                        continue;
+                    };
+                    state.stats.errors += 1;
+                    let reason = if err.is_panic() {
+                        "fetch task panicked"
+                    } else {
+                        "fetch task was aborted"
+                    };
+                    state
+                        .outcomes
+                        .insert(data.path.clone(), FetchOutcome::Error(reason.to_string()));
+                    state.failures.push(Failure {
+                        data,
+                        reason: reason.to_string(),
+                        transient: false,
+                    });
+                }
+            }
+        }
+        while let Some(Ok((path, result))) = state.preview_joins.try_join_next() {
+            state.preview_pending.remove(&path);
+            match result {
+                Ok(lyrics) => {
+                    state.preview_cache.put(path, lyrics);
+                }
+                Err(err) => {
+                    state.preview_failed.insert(path, err.to_string());
+                }
+            }
+        }
+        while let Some(Ok((data, result, complete))) = state.search_joins.try_join_next() {
+            // A partial result is shown but not cached, so reopening the picker asks again.
+            if complete && let Ok(candidates) = &result {
+                state
+                    .search_cache
+                    .put(search_key(&data), candidates.clone());
+            }
+            if state
+                .candidates_for
+                .as_ref()
+                .is_none_or(|shown| shown.path != data.path)
+            {
+                continue;
+            }
+            match result {
+                Ok(candidates) => state.show_candidates(&data, candidates),
+                Err(error) => state.candidates_status = Some(error.reason),
+            }
+        }
+        if let Some(found) = &mut state.scan_found {
+            let before = state.music.len();
+            while let Ok(data) = found.try_recv() {
+                state.music.push(data);
+            }
+            if state.music.len() > before {
+                state.refilter();
+            }
+        }
+        let mut index_changed = false;
+        while let Some(Ok((mut music, lyrics, update))) = state.rescan_joins.try_join_next() {
+            if let Some(update) = update {
+                index_changed |= Arc::make_mut(&mut state.index).merge(update);
+            }
+            let selected = state.selected_music().map(|data| data.path.clone());
+            apply_duration_overrides(&mut music, &state.duration_overrides);
+            let added = music.len() as isize - state.music.len() as isize;
+            state.music = music;
+            state.lyrics = lyrics;
+            for (path, lyrics) in &state.unsaved {
+                state.lyrics.insert(path.clone(), lyrics.status());
+            }
+            state.refilter();
+            if let Some(position) =
+                selected.and_then(|path| state.filtered_music().position(|data| data.path == path))
+            {
+                state.table_state.select(Some(position));
+            }
+            let message = if state.scan_found.take().is_some() {
+                format!("Loaded library, {} tracks", state.music.len())
+            } else {
+                format!(
+                    "Rescanned library, {} tracks ({added:+})",
+                    state.music.len()
+                )
+            };
+            state.record(message, None);
+        }
+        while let Some(Ok((folder, mut music, lyrics, update))) = state.folder_joins.try_join_next()
+        {
+            if let Some(update) = update {
+                index_changed |= Arc::make_mut(&mut state.index).merge(update);
+            }
+            apply_duration_overrides(&mut music, &state.duration_overrides);
+            state.lyrics.extend(lyrics);
+            for (path, lyrics) in &state.unsaved {
+                state.lyrics.insert(path.clone(), lyrics.status());
+            }
+            state.merge_folder(&folder, &music);
+            state.record(
+                format!("Loaded {} tracks from {}", music.len(), folder.display()),
+                None,
+            );
+            if state.fetch_after_load.remove(&folder) {
+                Func::scan_missing(&mut state, music);
+            }
+        }
+        if index_changed {
+            let index = state.index.clone();
+            state
+                .write_joins
+                .spawn(async move { index.save().await.map(|()| 0) });
+        }
+        while let Some(Ok(trashed)) = state.trash_joins.try_join_next() {
+            let Some(data) = trashed else {
+                continue;
+            };
+            state.announce(format!("Trashed lyrics for {}", data.title));
+            state.record(
+                format!("Deleted flagged lyrics for {} (kept a .bak)", data.title),
+                Some(data.path.clone()),
+            );
+            state.lyrics.insert(data.path.clone(), data.embedded);
+            state.preview_cache.pop(&data.path);
+            state.preview_failed.remove(&data.path);
+            state.search_cache.pop(&search_key(&data));
+            state.refetch_queue.push_back(data);
+        }
+        if state.screen == Screens::Main
+            && state.field.is_none()
+            && let Some(data) = state.refetch_queue.pop_front()
+        {
+            Func::open_candidates_for(&mut state, data);
+        }
+        while let Some(Ok((path, duration))) = state.duration_joins.try_join_next() {
+            match duration {
+                Some(duration) => {
+                    state.details_status = Some(format!("decoded duration: {duration}s"));
+                    state.set_duration_override(path, Some(duration));
+                }
+                None => state.details_status = Some("could not decode this file".to_string()),
+            }
+        }
+        state.request_preview();
+        while let Some(Ok(result)) = state.write_joins.try_join_next() {
+            state.stats.record_write(result);
+        }
+        while let Some(joined) = state.lyric_writes.try_join_next() {
+            state.writes_done += 1;
+            if let Ok(result) = joined {
+                state.stats.record_write(result);
+            }
+        }
+        while let Ok((path, err)) = state.hook_failures.try_recv() {
+            state.record(format!("Player hook failed: {err}"), Some(path));
+        }
+        if state.writes_done == state.writes_total {
+            state.writes_done = 0;
+            state.writes_total = 0;
         }
-        while let Some(Ok(_)) = state.write_joins.try_join_next() {}
 
-        let music = state.music.clone();
+        let music = state.filtered_music().cloned().collect::<Vec<_>>();
         let mut screen = Screen::default();
-        screen.tracks = screen.tracks.rows(
-            music
+        if state.settings.screen_reader {
+            screen.tracks = screen.tracks.highlight_symbol("> ");
+        }
+        state.announce_changes();
+        let icons = if state.settings.screen_reader {
+            StatusIcons::Text
+        } else {
+            state.settings.status_icons
+        };
+        let theme = state.settings.theme;
+        let queued = state.queued_at.get_or_insert_with(|| {
+            state
+                .fetch_queue
                 .iter()
-                .filter(|s| state.filter.apply(s))
-                .map(|s| s.to_row()),
-        );
+                .enumerate()
+                .map(|(position, data)| (data.path.clone(), position + 1))
+                .collect()
+        });
+        screen.tracks = screen
+            .tracks
+            .rows(music.iter().map(|s| {
+                let status = state.lyrics.get(&s.path).copied();
+                let row = s.to_row(
+                    Cell::from(icons.label(status)).style(theme.status(status)),
+                    queued.get(&s.path).copied(),
+                    state.flags.get(&s.path).copied(),
+                );
+                if state.marked.contains(&s.path) {
+                    row.style(theme.marked())
+                } else {
+                    row
+                }
+            }))
+            .row_highlight_style(theme.highlight())
+            .widths([
+                Constraint::Length(icons.width()),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+                Constraint::Length(4),
+            ]);
 
+        let frame_start = Instant::now();
         if let Err(e) = terminal.draw(|frame| {
             frame.render_stateful_widget(screen, frame.area(), &mut state);
+            if let Some(cursor) = state.cursor {
+                frame.set_cursor_position(cursor);
+            }
         }) {
             println!("Error: {}", e);
             break;
         }
+        state.debug.frame_time = frame_start.elapsed();
+        if let Some(event_at) = state.debug.event_at.take() {
+            state.debug.event_latency = event_at.elapsed();
+        }
 
-        if let Ok(true) = event::poll(Duration::from_millis(50)) {
-            match event::read() {
-                Ok(event) => state.event_handler(event, &keymap),
-                Err(_) => {}
-            }
-        };
+        if let Ok(true) = event::poll(Duration::from_millis(50))
+            && let Ok(event) = event::read()
+        {
+            state.debug.event_at = Some(Instant::now());
+            state.event_handler(event, &keymap);
+        }
 
         if state.will_quit {
             break;
         }
     }
-    ratatui::restore();
-}
-
-fn scan_music(path: PathBuf) -> Option<Vec<MusicData>> {
-    let dir = std::fs::read_dir(path);
-    let mut queue = VecDeque::new();
-    let mut vec = Vec::new();
-    queue.push_back(dir);
-    while !queue.is_empty() {
-        let Some(Ok(dir)) = queue.pop_front() else {
-            continue;
-        };
-
-        for entry in dir {
-            let Ok(entry) = entry else {
-                continue;
-            };
-            let Ok(metadata) = entry.metadata() else {
-                continue;
-            };
-            if metadata.is_dir() {
-                queue.push_back(std::fs::read_dir(entry.path()));
-            } else {
-                let Some(path) = entry
-                    .path()
-                    .extension()
-                    .and_then(|ext| ext.to_str())
-                    .and_then(|ext| {
-                        if MUSIC_EXTENSIONS.contains(&ext) {
-                            Some(entry.path())
-                        } else {
-                            None
-                        }
-                    })
-                else {
-                    continue;
-                };
-                vec.push(path);
-            }
-        }
+    state
+        .stats
+        .record_write(state.providers.save_cache().await.map(|()| 0));
+    while let Some(Ok(result)) = state.write_joins.join_next().await {
+        state.stats.record_write(result);
     }
-    let mut res = Vec::new();
-    for path in vec {
-        let Ok(data) = MusicData::from_file(path) else {
-            continue;
-        };
-        res.push(data);
+    while let Some(Ok(result)) = state.lyric_writes.join_next().await {
+        state.stats.record_write(result);
     }
-    return Some(res);
+    // Last, so an older snapshot still being written can't replace the final queue.
+    state.stats.record_write(
+        store::save(QUEUE_FILE, &state.pending_paths())
+            .await
+            .map(|()| 0),
+    );
+    let _ = crossterm::execute!(
+        std::io::stdout(),
+        event::DisableBracketedPaste,
+        event::DisableFocusChange
+    );
+    ratatui::restore();
+    state.stats.requests = state.providers.requests();
+    println!("{}", state.stats);
+    ExitCode::SUCCESS
 }
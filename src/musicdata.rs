@@ -1,18 +1,149 @@
-use std::{error::Error, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
 
-use ratatui::widgets::Row;
+use lofty::prelude::*;
+use ratatui::widgets::{Cell, Row};
 use serde::{Deserialize, Serialize};
 
+use crate::library::TrackFlag;
+use crate::lrc;
+use crate::providers::send_json;
+use crate::settings::{PlayerNotify, TimestampPrecision, WriteOptions};
+use crate::throttle::Throttle;
+
+pub const LRCLIB_URL: &str = "https://lrclib.net";
+
 #[derive(Clone, Debug)]
 pub struct MusicData {
     pub title: String,
-    pub artist: String,
-    pub album: String,
+    pub artist: Arc<str>,
+    pub album: Arc<str>,
     pub duration: usize,
+    pub disc: Option<u32>,
+    pub track: Option<u32>,
+    pub album_artist: Option<Arc<str>>,
+    pub compilation: bool,
+    pub embedded: LyricsStatus,
+    /// When the file appeared in the library: its creation time where the filesystem
+    /// records one, otherwise its modification time.
+    pub added: SystemTime,
     pub path: PathBuf,
+    pub keys: SearchKeys,
+}
+
+pub const VARIOUS_ARTISTS: &str = "Various Artists";
+const VARIOUS_ARTISTS_KEY: &str = "various artists";
+const COMPILATION_ARTISTS: usize = 3;
+
+/// Flags albums carrying three or more distinct track artists and no album
+/// artist as compilations, so they are grouped the way players show them.
+pub fn detect_compilations(music: &mut [MusicData]) {
+    let mut artists: HashMap<(&str, Option<&Path>), HashSet<&str>> = HashMap::new();
+    for data in music.iter().filter(|data| data.album_artist.is_none()) {
+        artists
+            .entry((&data.keys.album, data.path.parent()))
+            .or_default()
+            .insert(&data.keys.artist);
+    }
+    let compilations = artists
+        .into_iter()
+        .filter(|(_, artists)| artists.len() >= COMPILATION_ARTISTS)
+        .map(|((album, dir), _)| (album.to_string(), dir.map(Path::to_path_buf)))
+        .collect::<HashSet<_>>();
+    for data in music.iter_mut() {
+        let album = (
+            data.keys.album.to_string(),
+            data.path.parent().map(Path::to_path_buf),
+        );
+        if compilations.contains(&album) {
+            data.compilation = true;
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchKeys {
+    pub title: String,
+    pub artist: Arc<str>,
+    pub album: Arc<str>,
+    pub album_artist: Option<Arc<str>>,
+}
+
+pub fn fold(value: &str) -> String {
+    value.to_lowercase()
+}
+
+#[derive(Default)]
+pub struct Interner(HashSet<Arc<str>>);
+
+impl Interner {
+    pub fn intern(&mut self, value: &str) -> Arc<str> {
+        if let Some(interned) = self.0.get(value) {
+            return interned.clone();
+        }
+        let interned: Arc<str> = Arc::from(value);
+        self.0.insert(interned.clone());
+        interned
+    }
+
+    /// Swaps a track's strings for shared copies, for tracks read with an interner of their own.
+    pub fn share(&mut self, data: &mut MusicData) {
+        data.artist = self.intern(&data.artist);
+        data.album = self.intern(&data.album);
+        data.album_artist = data.album_artist.as_deref().map(|value| self.intern(value));
+        data.keys.artist = self.intern(&data.keys.artist);
+        data.keys.album = self.intern(&data.keys.album);
+        data.keys.album_artist = data
+            .keys
+            .album_artist
+            .as_deref()
+            .map(|value| self.intern(value));
+    }
 }
 
 #[derive(Debug, Clone)]
+pub struct QueryError {
+    pub reason: String,
+    pub transient: bool,
+}
+
+impl QueryError {
+    pub fn transient(reason: String) -> Self {
+        Self {
+            reason,
+            transient: true,
+        }
+    }
+
+    pub fn permanent(reason: String) -> Self {
+        Self {
+            reason,
+            transient: false,
+        }
+    }
+}
+
+impl From<reqwest::Error> for QueryError {
+    fn from(err: reqwest::Error) -> Self {
+        Self {
+            transient: err.is_timeout()
+                || err.is_connect()
+                || err.status().is_some_and(|status| {
+                    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                }),
+            // The URL can carry an API key in its query string.
+            reason: err.without_url().to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Lyrics {
     None,
     Synced(String),
@@ -20,118 +151,562 @@ pub enum Lyrics {
     Instrumental,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LyricsStatus {
+    None,
+    Synced,
+    Plain,
+    Instrumental,
+}
+
+impl std::fmt::Display for LyricsStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LyricsStatus::None => "none",
+            LyricsStatus::Synced => "synced",
+            LyricsStatus::Plain => "plain",
+            LyricsStatus::Instrumental => "instrumental",
+        })
+    }
+}
+
+impl LyricsStatus {
+    pub fn sidecar(&self, path: &Path) -> Option<PathBuf> {
+        match self {
+            LyricsStatus::Synced => Some(path.with_extension("lrc")),
+            LyricsStatus::Plain => Some(path.with_extension("txt")),
+            LyricsStatus::None | LyricsStatus::Instrumental => None,
+        }
+    }
+}
+
 impl Lyrics {
-    pub async fn to_file(&self, path: &PathBuf) -> Result<(), tokio::io::Error> {
-        let mut path = path.clone();
+    pub fn status(&self) -> LyricsStatus {
         match self {
-            Lyrics::None => Ok(()),
-            Lyrics::Synced(lrc) => {
-                path.set_extension("lrc");
-                Ok(tokio::fs::write(path, lrc).await?)
-            }
-            Lyrics::Plain(lrc) => {
-                path.set_extension("txt");
-                Ok(tokio::fs::write(path, lrc).await?)
+            Lyrics::None => LyricsStatus::None,
+            Lyrics::Synced(_) => LyricsStatus::Synced,
+            Lyrics::Plain(_) => LyricsStatus::Plain,
+            Lyrics::Instrumental => LyricsStatus::Instrumental,
+        }
+    }
+
+    /// Why these lyrics look like a provider glitch, such as an empty body or an error page,
+    /// rather than a song.
+    pub fn implausible(&self, options: &WriteOptions) -> Option<String> {
+        let (Lyrics::Synced(text) | Lyrics::Plain(text)) = self else {
+            return None;
+        };
+        if options.max_bytes > 0 && text.len() > options.max_bytes {
+            return Some(format!(
+                "{} KiB is over max_lyrics_bytes",
+                text.len().div_ceil(1024)
+            ));
+        }
+        if text.trim_start().starts_with('<') {
+            return Some("looks like an HTML page".to_string());
+        }
+        let lines = text
+            .lines()
+            .filter(|line| !lrc::leading_tags(line).1.trim().is_empty())
+            .count();
+        if lines < options.min_lines {
+            return Some(format!("only {lines} lines, under min_lyrics_lines"));
+        }
+        None
+    }
+
+    /// Like `write`, but refuses `implausible` lyrics with `ErrorKind::InvalidData`. For lyrics
+    /// from a provider; whatever the user typed or pasted is theirs to keep.
+    pub async fn write_checked(
+        &self,
+        path: &Path,
+        options: &WriteOptions,
+    ) -> Result<usize, tokio::io::Error> {
+        if let Some(reason) = self.implausible(options) {
+            return Err(tokio::io::Error::new(ErrorKind::InvalidData, reason));
+        }
+        self.write(path, options).await
+    }
+
+    pub async fn write(
+        &self,
+        path: &Path,
+        options: &WriteOptions,
+    ) -> Result<usize, tokio::io::Error> {
+        let normalized;
+        let lyrics = match self {
+            Lyrics::Synced(text) if options.precision != TimestampPrecision::Keep => {
+                normalized = Lyrics::Synced(lrc::normalize(text, options.precision));
+                &normalized
             }
-            Lyrics::Instrumental => Ok(()),
+            lyrics => lyrics,
+        };
+        let mut written = 0;
+        if options.output.sidecar() {
+            written += lyrics.to_file(path).await?;
         }
+        if options.output.embedded()
+            && let Lyrics::Synced(text) | Lyrics::Plain(text) = lyrics
+        {
+            let path = path.to_path_buf();
+            let text = text.clone();
+            let synced = matches!(lyrics, Lyrics::Synced(_));
+            written += text.len();
+            tokio::task::spawn_blocking(move || embed_lyrics(&path, text, synced))
+                .await
+                .map_err(tokio::io::Error::other)??;
+        }
+        Ok(written)
+    }
+
+    /// Runs once the lyrics for `path` are completely written, so players that cache missing
+    /// lyrics pick up the new ones. Kept apart from `write`, since the lyrics are saved
+    /// whether or not a player hook fails.
+    pub async fn notify_players(
+        &self,
+        path: &Path,
+        options: &WriteOptions,
+    ) -> Result<(), tokio::io::Error> {
+        notify_players(path, self.status().sidecar(path), &options.notify).await
+    }
+
+    /// Writes the lyrics next to `path`, returning the number of bytes written.
+    pub async fn to_file(&self, path: &Path) -> Result<usize, tokio::io::Error> {
+        let (Some(sidecar), Lyrics::Synced(lrc) | Lyrics::Plain(lrc)) =
+            (self.status().sidecar(path), self)
+        else {
+            return Ok(0);
+        };
+        // Write to a temporary name and rename, so a player never sees a half-written file.
+        let mut partial = sidecar.clone().into_os_string();
+        partial.push(".part");
+        tokio::fs::write(&partial, lrc).await?;
+        tokio::fs::rename(&partial, &sidecar).await?;
+        Ok(lrc.len())
+    }
+}
+
+async fn notify_players(
+    audio: &Path,
+    sidecar: Option<PathBuf>,
+    notify: &PlayerNotify,
+) -> Result<(), tokio::io::Error> {
+    if notify.touch_audio {
+        let audio = audio.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            std::fs::File::options()
+                .write(true)
+                .open(audio)?
+                .set_modified(std::time::SystemTime::now())
+        })
+        .await
+        .map_err(tokio::io::Error::other)??;
+    }
+    let Some((program, args)) = notify.hook.split_first() else {
+        return Ok(());
+    };
+    let lyrics = sidecar.unwrap_or_default();
+    let substitute = |arg: &String| {
+        arg.replace("{audio}", &audio.to_string_lossy())
+            .replace("{lyrics}", &lyrics.to_string_lossy())
+    };
+    let status = tokio::process::Command::new(substitute(program))
+        .args(args.iter().map(substitute))
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .status()
+        .await?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(tokio::io::Error::other(format!("write hook {status}")))
     }
 }
 
 impl<'a> MusicData {
-    pub fn to_row(&'a self) -> Row<'a> {
-        Row::new(vec![
-            self.title.to_string(),
-            self.artist.to_string(),
-            self.album.to_string(),
-        ])
+    pub fn group_artist(&self) -> &str {
+        if self.compilation {
+            VARIOUS_ARTISTS
+        } else {
+            self.album_artist.as_deref().unwrap_or(&self.artist)
+        }
     }
 
-    pub async fn query(&self, client: &reqwest::Client) -> Lyrics {
-        let response = client
-            .get("https://lrclib.net/api/get")
-            .query(&[
-                ["track_name", self.title.as_str()],
-                ["artist_name", self.artist.as_str()],
-                ["album_name", self.album.as_str()],
-                ["duration", self.duration.to_string().as_str()],
-            ])
-            .send()
-            .await;
-        match response {
-            Ok(response) => {
-                if !response.status().is_success() {
-                    return Lyrics::None;
-                }
-                let Ok(lyrics) = response.text().await else {
-                    return Lyrics::None;
-                };
-                match serde_json::from_str::<ApiResponse>(lyrics.as_str()) {
-                    Ok(lyrics_data) => {
-                        if let Some(lrc) = lyrics_data.synced_lyrics {
-                            Lyrics::Synced(lrc)
-                        } else if let Some(lrc) = lyrics_data.plain_lyrics {
-                            Lyrics::Plain(lrc)
-                        } else if lyrics_data.instrumental {
-                            Lyrics::Instrumental
-                        } else {
-                            Lyrics::None
-                        }
+    pub fn group_key(&self) -> &str {
+        if self.compilation {
+            VARIOUS_ARTISTS_KEY
+        } else {
+            self.keys
+                .album_artist
+                .as_deref()
+                .unwrap_or(&self.keys.artist)
+        }
+    }
+
+    pub fn album_order(&self) -> (&str, &str, u32, u32, &Path) {
+        (
+            self.group_key(),
+            &self.keys.album,
+            self.disc.unwrap_or(1),
+            self.track.unwrap_or(0),
+            &self.path,
+        )
+    }
+
+    pub fn to_row(
+        &'a self,
+        status: Cell<'a>,
+        queued: Option<usize>,
+        flag: Option<TrackFlag>,
+    ) -> Row<'a> {
+        let album = if self.compilation {
+            Cell::from(format!("{} ({})", self.album, self.group_artist()))
+        } else {
+            Cell::from(&*self.album)
+        };
+        Row::new([
+            status,
+            match (queued, flag) {
+                (None, None) => Cell::from(self.title.as_str()),
+                (queued, flag) => {
+                    let mut title = self.title.clone();
+                    if let Some(position) = queued {
+                        title.push_str(&format!(" (#{position} in queue)"));
+                    }
+                    if let Some(flag) = flag {
+                        title.push_str(&format!(" [{flag}]"));
                     }
-                    Err(err) => Lyrics::None,
+                    Cell::from(title)
                 }
-            }
-            Err(err) => Lyrics::None,
-        }
+            },
+            Cell::from(&*self.artist),
+            album,
+            Cell::from(self.age()),
+        ])
     }
 
-    pub async fn check_lyrics(&self) -> Result<Lyrics, tokio::io::Error> {
-        if let Ok(true) = self.path.with_extension("lrc").try_exists() {
-            let path = self.path.with_extension("lrc");
-            let lyrics = tokio::fs::read_to_string(path).await?;
+    /// How long ago the track was added, as `12d` or `3y`.
+    pub fn age(&self) -> String {
+        let days = SystemTime::now()
+            .duration_since(self.added)
+            .map_or(0, |age| age.as_secs() / (24 * 60 * 60));
+        match days {
+            0..365 => format!("{days}d"),
+            _ => format!("{}y", days / 365),
+        }
+    }
 
-            Ok(Lyrics::Synced(lyrics))
-        } else if let Ok(true) = self.path.with_extension("txt").try_exists() {
-            let path = self.path.with_extension("txt");
-            let lyrics = tokio::fs::read_to_string(path).await?;
+    pub async fn search(
+        &self,
+        client: &reqwest::Client,
+        throttle: &Throttle,
+        lrclib_url: &str,
+    ) -> Result<Vec<Candidate>, QueryError> {
+        let request = client
+            .get(format!("{}/api/search", lrclib_url.trim_end_matches('/')))
+            .query(&[
+                ["track_name", self.title.as_str()],
+                ["artist_name", &self.artist],
+            ]);
+        send_json(request, throttle).await
+    }
 
-            Ok(Lyrics::Plain(lyrics))
+    pub async fn check_lyrics(&self) -> Result<LyricsStatus, tokio::io::Error> {
+        if self.embedded == LyricsStatus::Synced
+            || tokio::fs::try_exists(self.path.with_extension("lrc")).await?
+        {
+            Ok(LyricsStatus::Synced)
+        } else if self.embedded == LyricsStatus::Plain
+            || tokio::fs::try_exists(self.path.with_extension("txt")).await?
+        {
+            Ok(LyricsStatus::Plain)
         } else {
-            Ok(Lyrics::None)
+            Ok(LyricsStatus::None)
         }
     }
 
-    pub fn from_file(flac_file: PathBuf) -> Result<MusicData, Box<dyn Error>> {
-        let tags = metaflac::Tag::read_from_path(&flac_file)?;
-
-        let Some(mut title) = tags.get_vorbis("TITLE") else {
-            return Err("No title found".into());
+    pub async fn read_lyrics(&self, status: LyricsStatus) -> Result<Lyrics, tokio::io::Error> {
+        let Some(sidecar) = status.sidecar(&self.path) else {
+            return Ok(match status {
+                LyricsStatus::Instrumental => Lyrics::Instrumental,
+                _ => Lyrics::None,
+            });
         };
-        let Some(mut artist) = tags.get_vorbis("ARTIST") else {
-            return Err("No artist found".into());
+        let lyrics = match tokio::fs::read_to_string(sidecar).await {
+            Ok(lyrics) => lyrics,
+            Err(err) if err.kind() == ErrorKind::NotFound && self.embedded == status => {
+                let path = self.path.clone();
+                return tokio::task::spawn_blocking(move || read_embedded(&path))
+                    .await
+                    .map_err(tokio::io::Error::other)?
+                    .ok_or(err);
+            }
+            Err(err) => return Err(err),
         };
-        let Some(mut album) = tags.get_vorbis("ALBUM") else {
-            return Err("No album found".into());
+        match status {
+            LyricsStatus::Synced => Ok(Lyrics::Synced(lyrics)),
+            _ => Ok(Lyrics::Plain(lyrics)),
+        }
+    }
+
+    /// Copies lyrics from the tags into a sidecar, returning the bytes written.
+    pub async fn extract_embedded(&self, overwrite: bool) -> Result<usize, tokio::io::Error> {
+        let Some(sidecar) = self.embedded.sidecar(&self.path) else {
+            return Ok(0);
         };
+        if !overwrite && tokio::fs::try_exists(&sidecar).await.unwrap_or(false) {
+            return Ok(0);
+        }
+        let path = self.path.clone();
+        let lyrics = tokio::task::spawn_blocking(move || read_embedded(&path))
+            .await
+            .map_err(tokio::io::Error::other)?;
+        match lyrics {
+            Some(lyrics) => lyrics.to_file(&self.path).await,
+            None => Ok(0),
+        }
+    }
 
-        let streaminfo = tags.get_streaminfo().unwrap();
-        let duration = streaminfo.total_samples as usize / streaminfo.sample_rate as usize;
+    pub fn from_file(path: PathBuf, interner: &mut Interner) -> Result<MusicData, ReadError> {
+        let tagged = lofty::probe::Probe::open(&path)?
+            .guess_file_type()
+            .map_err(lofty::error::FileParseError::from)?
+            .read()?;
+        let tags = tagged
+            .primary_tag()
+            .or_else(|| tagged.first_tag())
+            .ok_or(ReadError::NoTags)?;
+
+        let title = tags.title().ok_or(ReadError::MissingTag("title"))?;
+        let artist = tags.artist().ok_or(ReadError::MissingTag("artist"))?;
+        let album = tags.album().ok_or(ReadError::MissingTag("album"))?;
+        let album_artist = tags
+            .get_string(ItemKey::AlbumArtist)
+            .filter(|value| !value.is_empty());
+        let mut embedded =
+            embedded_lyrics(tags).map_or(LyricsStatus::None, |lyrics| lyrics.status());
+        if embedded == LyricsStatus::Plain && read_id3_synced(&path).is_some() {
+            embedded = LyricsStatus::Synced;
+        }
+        let compilation = tags
+            .get_string(ItemKey::FlagCompilation)
+            .and_then(|value| value.trim().parse::<u32>().ok())
+            .is_some_and(|value| value > 0);
+        // Tags can parse fine on files whose stream headers are damaged; a missing
+        // sample rate means the reported length can't be trusted either.
+        let added = std::fs::metadata(&path)
+            .and_then(|metadata| metadata.created().or_else(|_| metadata.modified()))
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let properties = tagged.properties();
+        let duration = if properties.sample_rate().is_some_and(|rate| rate > 0) {
+            properties.duration().as_secs() as usize
+        } else {
+            0
+        };
 
         Ok(MusicData {
-            title: title.next().unwrap_or_default().to_string(),
-            artist: artist.next().unwrap_or_default().to_string(),
-            album: album.next().unwrap_or_default().to_string(),
+            title: title.to_string(),
+            artist: interner.intern(&artist),
+            album: interner.intern(&album),
             duration,
-            path: flac_file,
+            disc: tags.disk(),
+            track: tags.track(),
+            album_artist: album_artist.map(|value| interner.intern(value)),
+            compilation,
+            embedded,
+            added,
+            keys: SearchKeys {
+                title: fold(&title),
+                artist: interner.intern(&fold(&artist)),
+                album: interner.intern(&fold(&album)),
+                album_artist: album_artist.map(|value| interner.intern(&fold(value))),
+            },
+            path,
         })
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct ApiResponse {
-    instrumental: bool,
-    #[serde(rename = "plainLyrics")]
-    plain_lyrics: Option<String>,
-    #[serde(rename = "syncedLyrics")]
-    synced_lyrics: Option<String>,
+fn embedded_lyrics(tags: &lofty::tag::Tag) -> Option<Lyrics> {
+    let text = tags
+        .get_string(ItemKey::Lyrics)
+        .or_else(|| tags.get_string(ItemKey::UnsyncLyrics))
+        .filter(|text| !text.trim().is_empty())?;
+    if lrc::validate(text).timed_lines > 0 {
+        Some(Lyrics::Synced(text.to_string()))
+    } else {
+        Some(Lyrics::Plain(text.to_string()))
+    }
+}
+
+fn embed_lyrics(path: &Path, text: String, synced: bool) -> Result<(), tokio::io::Error> {
+    if path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("mp3"))
+    {
+        return embed_id3_lyrics(path, text, synced);
+    }
+    let mut tagged = lofty::probe::Probe::open(path)
+        .map_err(tokio::io::Error::other)?
+        .guess_file_type()?
+        .read()
+        .map_err(tokio::io::Error::other)?;
+    if tagged.primary_tag_mut().is_none() {
+        tagged.insert_tag(lofty::tag::Tag::new(tagged.primary_tag_type()));
+    }
+    let Some(tags) = tagged.primary_tag_mut() else {
+        return Err(tokio::io::Error::other("format does not support tags"));
+    };
+    tags.insert_text(ItemKey::Lyrics, text);
+    tagged
+        .save_to_path(path, lofty::config::WriteOptions::default())
+        .map_err(tokio::io::Error::other)
+}
+
+/// MP3 gets a SYLT frame for synced lyrics, alongside a USLT frame for players without SYLT.
+fn embed_id3_lyrics(path: &Path, text: String, synced: bool) -> Result<(), tokio::io::Error> {
+    use id3::{TagLike, frame};
+
+    let mut tag = match id3::Tag::read_from_path(path) {
+        Ok(tag) => tag,
+        Err(err) if matches!(err.kind, id3::ErrorKind::NoTag) => id3::Tag::new(),
+        Err(err) => return Err(tokio::io::Error::other(err)),
+    };
+    tag.remove_all_lyrics();
+    tag.remove_all_synchronised_lyrics();
+    let plain = if synced {
+        let lines = lrc::timed_lines(&text);
+        let plain = lines
+            .iter()
+            .map(|&(_, line)| line)
+            .collect::<Vec<_>>()
+            .join("\n");
+        tag.add_frame(frame::SynchronisedLyrics {
+            lang: "eng".to_string(),
+            timestamp_format: frame::TimestampFormat::Ms,
+            content_type: frame::SynchronisedLyricsType::Lyrics,
+            description: String::new(),
+            content: lines
+                .into_iter()
+                .map(|(timestamp, line)| (timestamp.as_millis() as u32, line.to_string()))
+                .collect(),
+        });
+        plain
+    } else {
+        text
+    };
+    tag.add_frame(frame::Lyrics {
+        lang: "eng".to_string(),
+        description: String::new(),
+        text: plain,
+    });
+    tag.write_to_path(path, id3::Version::Id3v24)
+        .map_err(tokio::io::Error::other)
+}
+
+/// Rebuilds LRC text from an MP3's SYLT frame, which lofty only exposes as raw bytes.
+fn read_id3_synced(path: &Path) -> Option<Lyrics> {
+    if !path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("mp3"))
+    {
+        return None;
+    }
+    let tag = id3::Tag::read_from_path(path).ok()?;
+    let sylt = tag
+        .synchronised_lyrics()
+        .find(|sylt| sylt.timestamp_format == id3::frame::TimestampFormat::Ms)?;
+    let lrc = sylt
+        .content
+        .iter()
+        .map(|(millis, line)| {
+            format!(
+                "[{:02}:{:02}.{:02}]{line}",
+                millis / 60_000,
+                millis / 1000 % 60,
+                millis % 1000 / 10
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(Lyrics::Synced(lrc))
+}
+
+fn read_embedded(path: &Path) -> Option<Lyrics> {
+    if let Some(lyrics) = read_id3_synced(path) {
+        return Some(lyrics);
+    }
+    let tagged = lofty::probe::Probe::open(path)
+        .ok()?
+        .guess_file_type()
+        .ok()?
+        .read()
+        .ok()?;
+    let tags = tagged.primary_tag().or_else(|| tagged.first_tag())?;
+    embedded_lyrics(tags)
+}
+
+#[derive(Debug)]
+pub enum ReadError {
+    Unreadable(lofty::error::FileParseError),
+    NoTags,
+    MissingTag(&'static str),
+}
+
+impl std::fmt::Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadError::Unreadable(err) => write!(f, "unreadable file: {err}"),
+            ReadError::NoTags => f.write_str("no tags found"),
+            ReadError::MissingTag(tag) => write!(f, "no {tag} found"),
+        }
+    }
+}
+
+impl Error for ReadError {}
+
+impl From<lofty::error::FileParseError> for ReadError {
+    fn from(err: lofty::error::FileParseError) -> Self {
+        ReadError::Unreadable(err)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Candidate {
+    pub id: u64,
+    pub track_name: String,
+    pub artist_name: String,
+    pub album_name: String,
+    pub duration: f64,
+    #[serde(default)]
+    pub instrumental: bool,
+    pub plain_lyrics: Option<String>,
+    pub synced_lyrics: Option<String>,
+    /// The provider offering this candidate; lrclib search results leave it unset.
+    #[serde(skip)]
+    pub source: Option<&'static str>,
+    /// Which version of the lyrics this is, such as "translated", for providers with several.
+    #[serde(skip)]
+    pub variant: Option<&'static str>,
+}
+
+/// A search result with how closely it matches the track; 1 is a perfect match.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Scored {
+    pub candidate: Candidate,
+    pub score: f64,
+}
+
+impl Candidate {
+    pub fn lyrics(&self) -> Lyrics {
+        if let Some(lrc) = &self.synced_lyrics {
+            Lyrics::Synced(lrc.clone())
+        } else if let Some(lrc) = &self.plain_lyrics {
+            Lyrics::Plain(lrc.clone())
+        } else if self.instrumental {
+            Lyrics::Instrumental
+        } else {
+            Lyrics::None
+        }
+    }
 }
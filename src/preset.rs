@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use crossterm::event::KeyCode;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::Theme;
+use crate::{Func, KEYMAP, KeyBind, Screens};
+
+pub const PRESET_FILE: &str = "preset.ron";
+
+/// A shareable keymap and theme. Bindings are layered over the built-in keymap, and the
+/// theme, when set, wins over the one in the config file.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Preset {
+    #[serde(default)]
+    pub theme: Option<Theme>,
+    #[serde(default)]
+    pub keys: Vec<Binding>,
+}
+
+/// Kept as plain strings so an unknown screen, key or action is reported by name instead of
+/// failing the whole file.
+#[derive(Serialize, Deserialize)]
+pub struct Binding {
+    pub screen: String,
+    pub key: String,
+    pub action: String,
+}
+
+impl Binding {
+    fn new(bind: &KeyBind, func: Func) -> Self {
+        Self {
+            screen: format!("{:?}", bind.screen),
+            key: key_name(bind.keycode),
+            action: format!("{func:?}"),
+        }
+    }
+
+    fn parse(&self) -> Result<(KeyBind, Func), String> {
+        let screen = ron::from_str::<Screens>(&self.screen)
+            .map_err(|_| format!("unknown screen `{}`", self.screen))?;
+        let keycode = parse_key(&self.key).ok_or_else(|| format!("unknown key `{}`", self.key))?;
+        let func = ron::from_str::<Func>(&self.action).map_err(|_| {
+            format!(
+                "unknown action `{}` for `{}` on {}",
+                self.action, self.key, self.screen
+            )
+        })?;
+        Ok((KeyBind { screen, keycode }, func))
+    }
+}
+
+impl Preset {
+    /// The built-in keymap with every binding from this preset, plus one message per binding
+    /// that did not parse.
+    pub fn keymap(&self) -> (HashMap<KeyBind, Func>, Vec<String>) {
+        let mut keymap = KEYMAP.into_iter().collect::<HashMap<_, _>>();
+        let mut errors = Vec::new();
+        for binding in &self.keys {
+            match binding.parse() {
+                Ok((bind, func)) => {
+                    keymap.insert(bind, func);
+                }
+                Err(err) => errors.push(err),
+            }
+        }
+        (keymap, errors)
+    }
+
+    /// This preset's effective keymap written out in full, built-in bindings included.
+    pub fn export(&self, theme: Theme) -> Self {
+        let (keymap, _) = self.keymap();
+        let mut keys = keymap
+            .iter()
+            .map(|(bind, &func)| Binding::new(bind, func))
+            .collect::<Vec<_>>();
+        keys.sort_by(|a, b| (&a.screen, &a.key).cmp(&(&b.screen, &b.key)));
+        Self {
+            theme: Some(self.theme.unwrap_or(theme)),
+            keys,
+        }
+    }
+}
+
+fn key_name(keycode: KeyCode) -> String {
+    match keycode {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("F{n}"),
+        other => format!("{other:?}"),
+    }
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+    match name {
+        "Space" => Some(KeyCode::Char(' ')),
+        "Enter" => Some(KeyCode::Enter),
+        "Esc" => Some(KeyCode::Esc),
+        "Backspace" => Some(KeyCode::Backspace),
+        "Tab" => Some(KeyCode::Tab),
+        "Up" => Some(KeyCode::Up),
+        "Down" => Some(KeyCode::Down),
+        "Left" => Some(KeyCode::Left),
+        "Right" => Some(KeyCode::Right),
+        "Home" => Some(KeyCode::Home),
+        "End" => Some(KeyCode::End),
+        "PageUp" => Some(KeyCode::PageUp),
+        "PageDown" => Some(KeyCode::PageDown),
+        _ => name
+            .strip_prefix('F')
+            .and_then(|n| n.parse().ok())
+            .map(KeyCode::F),
+    }
+}
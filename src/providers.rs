@@ -0,0 +1,292 @@
+use std::{collections::HashMap, future::Future, pin::Pin, sync::Arc};
+
+use serde::de::DeserializeOwned;
+
+use crate::cache::ResponseCache;
+use crate::musicdata::{Candidate, Lyrics, MusicData, QueryError, Scored};
+use crate::settings::{RetryPolicy, Settings};
+use crate::throttle::{Throttle, retry_after};
+
+mod lrclib;
+mod musixmatch;
+mod netease;
+mod qq;
+
+pub use lrclib::Lrclib;
+pub use musixmatch::{MUSIXMATCH_URL, Musixmatch};
+pub use netease::{NETEASE_SEARCH_URL, Netease};
+pub use qq::{QQ_SEARCH_URL, QqMusic};
+
+pub type ProviderResult = Result<Lyrics, QueryError>;
+/// Lyrics, or `Lyrics::None` alongside the search matches too loose to save outright.
+pub type ScoredResult = Result<(Lyrics, Vec<Scored>), QueryError>;
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+pub trait LyricsProvider: Send + Sync {
+    /// The name used to select this provider in the config.
+    fn name(&self) -> &'static str;
+    fn fetch<'a>(&'a self, data: &'a MusicData) -> BoxFuture<'a, ProviderResult>;
+    /// Like `fetch`, but keeps borderline search matches for review. Providers that only
+    /// return exact matches have none. With `bypass_cache`, cached responses are ignored and
+    /// replaced by what the provider says now.
+    fn fetch_scored<'a>(
+        &'a self,
+        data: &'a MusicData,
+        _bypass_cache: bool,
+    ) -> BoxFuture<'a, ScoredResult> {
+        Box::pin(async move { Ok((self.fetch(data).await?, Vec::new())) })
+    }
+    /// Labelled alternative versions of the lyrics for the candidate picker, if the
+    /// provider offers any.
+    fn variants<'a>(
+        &'a self,
+        _data: &'a MusicData,
+    ) -> BoxFuture<'a, Result<Vec<Candidate>, QueryError>> {
+        Box::pin(async { Ok(Vec::new()) })
+    }
+}
+
+pub struct Fetched {
+    pub lyrics: Lyrics,
+    /// The provider that found the lyrics, if any did.
+    pub provider: Option<&'static str>,
+    /// Borderline search matches, best first, when no provider had a confident one.
+    pub review: Vec<Scored>,
+}
+
+#[derive(Clone, Default)]
+pub struct Registry {
+    providers: HashMap<&'static str, Arc<dyn LyricsProvider>>,
+    /// Tried after the configured providers come up empty, unless already configured.
+    fallbacks: Vec<&'static str>,
+    retry: RetryPolicy,
+    /// Every throttle the providers share, for scaling concurrency back after rate limits.
+    throttles: Vec<(&'static str, Arc<Throttle>)>,
+    cache: Option<Arc<ResponseCache>>,
+}
+
+impl Registry {
+    pub fn new(
+        settings: &Settings,
+        client: &reqwest::Client,
+        lrclib_throttle: Arc<Throttle>,
+        cache: Option<Arc<ResponseCache>>,
+    ) -> Self {
+        let netease_throttle = Arc::new(Throttle::new(
+            settings.politeness_for("netease", NETEASE_SEARCH_URL),
+        ));
+        let qq_throttle = Arc::new(Throttle::new(settings.politeness_for("qq", QQ_SEARCH_URL)));
+        let mut registry = Self {
+            retry: settings.retry.clone(),
+            throttles: vec![
+                ("lrclib", lrclib_throttle.clone()),
+                ("netease", netease_throttle.clone()),
+                ("qq", qq_throttle.clone()),
+            ],
+            cache: cache.clone(),
+            ..Self::default()
+        };
+        registry.register(Arc::new(Lrclib::new(
+            client.clone(),
+            lrclib_throttle,
+            &settings.lrclib_url,
+            cache,
+            settings.auto_accept_score,
+        )));
+        registry.register(Arc::new(Netease::new(
+            client.clone(),
+            netease_throttle,
+            settings.netease_translation,
+        )));
+        registry.register(Arc::new(QqMusic::new(client.clone(), qq_throttle)));
+        if let Some(api_key) = &settings.musixmatch_api_key {
+            let throttle = Arc::new(Throttle::new(
+                settings.politeness_for("musixmatch", MUSIXMATCH_URL),
+            ));
+            registry.throttles.push(("musixmatch", throttle.clone()));
+            registry.register(Arc::new(Musixmatch::new(
+                client.clone(),
+                throttle,
+                api_key.clone(),
+            )));
+            registry.fallbacks.push("musixmatch");
+        }
+        registry
+    }
+
+    pub fn register(&mut self, provider: Arc<dyn LyricsProvider>) {
+        self.providers.insert(provider.name(), provider);
+    }
+
+    /// Writes the response cache out, if one is configured.
+    pub async fn save_cache(&self) -> Result<(), tokio::io::Error> {
+        match &self.cache {
+            Some(cache) => cache.save().await,
+            None => Ok(()),
+        }
+    }
+
+    /// How many fetches to keep in flight given recent rate limits from any provider.
+    pub fn concurrency(&self, limit: usize) -> usize {
+        self.throttles
+            .iter()
+            .map(|(_, throttle)| throttle.concurrency(limit))
+            .min()
+            .unwrap_or(limit)
+    }
+
+    /// Requests sent to each provider so far, including candidate searches through lrclib's
+    /// throttle.
+    pub fn requests(&self) -> Vec<(&'static str, usize)> {
+        self.throttles
+            .iter()
+            .map(|(name, throttle)| (*name, throttle.requests()))
+            .collect()
+    }
+
+    /// Retries transient failures with exponential backoff before giving up on `provider`.
+    async fn fetch_from(
+        &self,
+        provider: &dyn LyricsProvider,
+        data: &MusicData,
+        bypass_cache: bool,
+    ) -> ScoredResult {
+        let mut retry = 0;
+        loop {
+            match provider.fetch_scored(data, bypass_cache).await {
+                Err(err) if err.transient && retry + 1 < self.retry.attempts => {
+                    tokio::time::sleep(self.retry.delay(retry)).await;
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Collects the variants the named providers offer for `data`, skipping unknown names and
+    /// providers that fail, and whether every provider answered.
+    pub async fn variants(&self, names: &[String], data: &MusicData) -> (Vec<Candidate>, bool) {
+        let mut variants = Vec::new();
+        let mut complete = true;
+        for name in names {
+            let Some(provider) = self.providers.get(name.as_str()) else {
+                continue;
+            };
+            match provider.variants(data).await {
+                Ok(found) => variants.extend(found),
+                Err(_) => complete = false,
+            }
+        }
+        (variants, complete)
+    }
+
+    /// Tries the named providers in order, skipping unknown names, until one has lyrics.
+    /// `bypass_cache` asks the providers again instead of reusing cached responses.
+    pub async fn fetch(
+        &self,
+        names: &[String],
+        data: &MusicData,
+        bypass_cache: bool,
+    ) -> Result<Fetched, QueryError> {
+        // Without a length, providers happily return some other song's lyrics.
+        if data.duration == 0 {
+            return Err(QueryError::permanent(
+                "audio length unknown; set or recompute it in the details popup".to_string(),
+            ));
+        }
+        let configured = names
+            .iter()
+            .filter_map(|name| self.providers.get(name.as_str()))
+            .collect::<Vec<_>>();
+        if configured.is_empty() {
+            return Err(QueryError::permanent(format!(
+                "no supported provider in {}",
+                names.join(", ")
+            )));
+        }
+        let fallbacks = self
+            .fallbacks
+            .iter()
+            .filter(|name| !names.iter().any(|configured| configured == *name))
+            .map(|name| &self.providers[name]);
+        let mut review = Vec::new();
+        // One source failing is when the next one matters most; the error only counts if
+        // nothing else turns up. A transient one wins so the track is retried.
+        let mut failure: Option<QueryError> = None;
+        for provider in configured.into_iter().chain(fallbacks) {
+            match self.fetch_from(provider.as_ref(), data, bypass_cache).await {
+                Ok((Lyrics::None, borderline)) => review.extend(borderline),
+                Ok((lyrics, _)) => {
+                    return Ok(Fetched {
+                        lyrics,
+                        provider: Some(provider.name()),
+                        review: Vec::new(),
+                    });
+                }
+                Err(err) => {
+                    if failure
+                        .as_ref()
+                        .is_none_or(|kept| !kept.transient && err.transient)
+                    {
+                        failure = Some(err);
+                    }
+                }
+            }
+        }
+        if review.is_empty()
+            && let Some(err) = failure
+        {
+            return Err(err);
+        }
+        review.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(Fetched {
+            lyrics: Lyrics::None,
+            provider: None,
+            review,
+        })
+    }
+}
+
+/// Sends a request through `throttle` and parses the JSON body, feeding rate limits and
+/// server errors back into the throttle.
+pub async fn send_json<T: DeserializeOwned>(
+    request: reqwest::RequestBuilder,
+    throttle: &Throttle,
+) -> Result<T, QueryError> {
+    find_json(request, throttle).await?.ok_or_else(|| {
+        QueryError::permanent(format!(
+            "server responded {}",
+            reqwest::StatusCode::NOT_FOUND
+        ))
+    })
+}
+
+/// Like [`send_json`], but a 404 is `None` instead of an error, for lookups where "not
+/// found" is an answer.
+pub async fn find_json<T: DeserializeOwned>(
+    request: reqwest::RequestBuilder,
+    throttle: &Throttle,
+) -> Result<Option<T>, QueryError> {
+    throttle.wait().await;
+    let response = request.send().await.map_err(QueryError::from)?;
+    let status = response.status();
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        throttle.rate_limited(retry_after(&response));
+        return Err(QueryError::transient(format!("server responded {status}")));
+    }
+    if status.is_server_error() {
+        throttle.penalize();
+        return Err(QueryError::transient(format!("server responded {status}")));
+    }
+    throttle.reward();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !status.is_success() {
+        return Err(QueryError::permanent(format!("server responded {status}")));
+    }
+    let body = response.text().await.map_err(QueryError::from)?;
+    serde_json::from_str::<T>(&body)
+        .map(Some)
+        .map_err(|err| QueryError::permanent(format!("invalid response: {err}")))
+}
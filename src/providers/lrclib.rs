@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cache::ResponseCache;
+use crate::musicdata::{Candidate, Lyrics, MusicData, Scored};
+use crate::providers::{BoxFuture, LyricsProvider, ProviderResult, ScoredResult, find_json};
+use crate::throttle::Throttle;
+use crate::variants::{QueryTerms, query_variants, similarity};
+
+const MIN_TITLE_SIMILARITY: f64 = 0.7;
+const MIN_ARTIST_SIMILARITY: f64 = 0.5;
+/// Search results further than this from the track's length are never picked.
+const MAX_DURATION_OFFSET: f64 = 10.0;
+
+pub struct Lrclib {
+    client: reqwest::Client,
+    throttle: Arc<Throttle>,
+    base_url: String,
+    get_url: String,
+    cache: Option<Arc<ResponseCache>>,
+    auto_accept: f64,
+}
+
+impl Lrclib {
+    pub fn new(
+        client: reqwest::Client,
+        throttle: Arc<Throttle>,
+        base_url: &str,
+        cache: Option<Arc<ResponseCache>>,
+        auto_accept: f64,
+    ) -> Self {
+        Self {
+            client,
+            throttle,
+            base_url: base_url.to_string(),
+            get_url: format!("{}/api/get", base_url.trim_end_matches('/')),
+            cache,
+            auto_accept,
+        }
+    }
+
+    async fn cached_query(&self, data: &MusicData, bypass_cache: bool) -> ScoredResult {
+        if !bypass_cache && let Some(lyrics) = self.cache.as_ref().and_then(|cache| cache.get(data))
+        {
+            return Ok((lyrics, Vec::new()));
+        }
+        let (lyrics, review) = self.query(data).await?;
+        // Left uncached so the next fetch offers the borderline matches for review again.
+        if let Some(cache) = &self.cache
+            && review.is_empty()
+        {
+            cache.put(data, &lyrics);
+        }
+        Ok((lyrics, review))
+    }
+
+    async fn query(&self, data: &MusicData) -> ScoredResult {
+        for terms in query_variants(&data.title, &data.artist, &data.album) {
+            match self.query_terms(data, &terms).await? {
+                Lyrics::None => continue,
+                lyrics => return Ok((lyrics, Vec::new())),
+            }
+        }
+        let candidates = data
+            .search(&self.client, &self.throttle, &self.base_url)
+            .await?;
+        let scored = score_candidates(data, candidates);
+        match scored.first() {
+            Some(best) if best.score >= self.auto_accept => {
+                Ok((best.candidate.lyrics(), Vec::new()))
+            }
+            _ => Ok((Lyrics::None, scored)),
+        }
+    }
+
+    async fn query_terms(&self, data: &MusicData, terms: &QueryTerms) -> ProviderResult {
+        let request = self.client.get(&self.get_url).query(&[
+            ["track_name", terms.title.as_str()],
+            ["artist_name", terms.artist.as_str()],
+            ["album_name", terms.album.as_str()],
+            ["duration", data.duration.to_string().as_str()],
+        ]);
+        let Some(lyrics_data) = find_json::<ApiResponse>(request, &self.throttle).await? else {
+            return Ok(Lyrics::None);
+        };
+        if let Some(lrc) = lyrics_data.synced_lyrics {
+            Ok(Lyrics::Synced(lrc))
+        } else if let Some(lrc) = lyrics_data.plain_lyrics {
+            Ok(Lyrics::Plain(lrc))
+        } else if lyrics_data.instrumental {
+            Ok(Lyrics::Instrumental)
+        } else {
+            Ok(Lyrics::None)
+        }
+    }
+}
+
+/// Scores search results by closeness to the track's title, artist and length, best first,
+/// preferring synced lyrics among equally good matches and dropping clear mismatches.
+fn score_candidates(data: &MusicData, candidates: Vec<Candidate>) -> Vec<Scored> {
+    let mut scored = candidates
+        .into_iter()
+        .filter(|candidate| !matches!(candidate.lyrics(), Lyrics::None))
+        .filter_map(|candidate| {
+            let title = similarity(&data.title, &candidate.track_name);
+            let artist = similarity(&data.artist, &candidate.artist_name);
+            let offset = (candidate.duration - data.duration as f64).abs();
+            if title < MIN_TITLE_SIMILARITY
+                || artist < MIN_ARTIST_SIMILARITY
+                || offset > MAX_DURATION_OFFSET
+            {
+                return None;
+            }
+            let synced = if candidate.synced_lyrics.is_some() {
+                0.05
+            } else {
+                0.0
+            };
+            let score = title * 0.5 + artist * 0.3 + (1.0 - offset / MAX_DURATION_OFFSET) * 0.2;
+            Some(Scored {
+                candidate,
+                score: score + synced,
+            })
+        })
+        .collect::<Vec<_>>();
+    scored.sort_by(|a, b| b.score.total_cmp(&a.score));
+    scored
+}
+
+impl LyricsProvider for Lrclib {
+    fn name(&self) -> &'static str {
+        "lrclib"
+    }
+
+    fn fetch<'a>(&'a self, data: &'a MusicData) -> BoxFuture<'a, ProviderResult> {
+        Box::pin(async move { Ok(self.cached_query(data, false).await?.0) })
+    }
+
+    fn fetch_scored<'a>(
+        &'a self,
+        data: &'a MusicData,
+        bypass_cache: bool,
+    ) -> BoxFuture<'a, ScoredResult> {
+        Box::pin(self.cached_query(data, bypass_cache))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ApiResponse {
+    instrumental: bool,
+    #[serde(rename = "plainLyrics")]
+    plain_lyrics: Option<String>,
+    #[serde(rename = "syncedLyrics")]
+    synced_lyrics: Option<String>,
+}
@@ -0,0 +1,172 @@
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::lrc;
+use crate::musicdata::{Lyrics, MusicData, QueryError};
+use crate::providers::{BoxFuture, LyricsProvider, ProviderResult, send_json};
+use crate::throttle::Throttle;
+
+pub const MUSIXMATCH_URL: &str = "https://api.musixmatch.com/ws/1.1/";
+const DURATION_DEVIATION: &str = "5";
+/// Free API keys append this notice to a truncated lyrics body.
+const NOTICE_MARKER: &str = "*******";
+
+pub struct Musixmatch {
+    client: reqwest::Client,
+    throttle: Arc<Throttle>,
+    api_key: String,
+}
+
+impl Musixmatch {
+    pub fn new(client: reqwest::Client, throttle: Arc<Throttle>, api_key: String) -> Self {
+        Self {
+            client,
+            throttle,
+            api_key,
+        }
+    }
+
+    async fn call<T: for<'de> Deserialize<'de>>(
+        &self,
+        method: &str,
+        data: &MusicData,
+        extra: &[(&str, &str)],
+    ) -> Result<Response<T>, QueryError> {
+        let request = self
+            .client
+            .get(format!("{MUSIXMATCH_URL}{method}"))
+            .query(&[
+                ("q_track", data.title.as_str()),
+                ("q_artist", &data.artist),
+                ("apikey", &self.api_key),
+            ])
+            .query(extra);
+        send_json(request, &self.throttle).await
+    }
+
+    /// The HTTP status is always 200; the real one is in the body.
+    fn body<T>(&self, response: Response<T>) -> Result<Option<T>, QueryError> {
+        match response.message.header.status_code {
+            200 => match response.message.body {
+                Body::Found(body) => Ok(Some(body)),
+                Body::Empty(_) => Ok(None),
+            },
+            404 => Ok(None),
+            401 => Err(QueryError::permanent(
+                "Musixmatch rejected the API key".to_string(),
+            )),
+            402 => {
+                self.throttle.rate_limited(None);
+                Err(QueryError::transient(
+                    "Musixmatch API quota exceeded".to_string(),
+                ))
+            }
+            code => Err(QueryError::transient(format!(
+                "Musixmatch responded {code}"
+            ))),
+        }
+    }
+
+    async fn query(&self, data: &MusicData) -> ProviderResult {
+        let duration = data.duration.to_string();
+        let response = self
+            .call::<SubtitleBody>(
+                "matcher.subtitle.get",
+                data,
+                &[
+                    ("f_subtitle_length", &duration),
+                    ("f_subtitle_length_max_deviation", DURATION_DEVIATION),
+                ],
+            )
+            .await?;
+        let subtitle = match response.message.header.status_code {
+            200 | 401 | 402 => self.body(response)?,
+            // Keys without subtitle access are refused here but may still get plain lyrics.
+            _ => None,
+        };
+        if let Some(body) = subtitle
+            && lrc::validate(&body.subtitle.subtitle_body).timed_lines > 0
+        {
+            return Ok(Lyrics::Synced(body.subtitle.subtitle_body));
+        }
+        let response = self
+            .call::<LyricsBody>("matcher.lyrics.get", data, &[])
+            .await?;
+        let Some(body) = self.body(response)? else {
+            return Ok(Lyrics::None);
+        };
+        if body.lyrics.instrumental != 0 {
+            return Ok(Lyrics::Instrumental);
+        }
+        // Saving the fragment would pass it off as the song's lyrics.
+        if body.lyrics.lyrics_body.contains(NOTICE_MARKER) {
+            return Err(QueryError::permanent(
+                "lyrics truncated by the free API tier".to_string(),
+            ));
+        }
+        let text = body.lyrics.lyrics_body.trim().to_string();
+        if text.is_empty() {
+            Ok(Lyrics::None)
+        } else {
+            Ok(Lyrics::Plain(text))
+        }
+    }
+}
+
+impl LyricsProvider for Musixmatch {
+    fn name(&self) -> &'static str {
+        "musixmatch"
+    }
+
+    fn fetch<'a>(&'a self, data: &'a MusicData) -> BoxFuture<'a, ProviderResult> {
+        Box::pin(self.query(data))
+    }
+}
+
+#[derive(Deserialize)]
+struct Response<T> {
+    message: Message<T>,
+}
+
+#[derive(Deserialize)]
+struct Message<T> {
+    header: Header,
+    body: Body<T>,
+}
+
+/// An empty list rather than an object when there is no result.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Body<T> {
+    Found(T),
+    Empty(serde::de::IgnoredAny),
+}
+
+#[derive(Deserialize)]
+struct Header {
+    status_code: u32,
+}
+
+#[derive(Deserialize)]
+struct SubtitleBody {
+    subtitle: Subtitle,
+}
+
+#[derive(Deserialize)]
+struct Subtitle {
+    subtitle_body: String,
+}
+
+#[derive(Deserialize)]
+struct LyricsBody {
+    lyrics: LyricsData,
+}
+
+#[derive(Deserialize)]
+struct LyricsData {
+    #[serde(default)]
+    lyrics_body: String,
+    #[serde(default)]
+    instrumental: u8,
+}
@@ -0,0 +1,212 @@
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::lrc;
+use crate::musicdata::{Candidate, Lyrics, MusicData, QueryError, fold};
+use crate::providers::{BoxFuture, LyricsProvider, ProviderResult, send_json};
+use crate::throttle::Throttle;
+
+pub const NETEASE_SEARCH_URL: &str = "https://music.163.com/api/search/get/web";
+pub const NETEASE_LYRIC_URL: &str = "https://music.163.com/api/song/lyric";
+/// Songs further than this from the track's duration are not considered matches.
+const DURATION_TOLERANCE_MS: i64 = 5000;
+const SEARCH_LIMIT: &str = "10";
+
+pub struct Netease {
+    client: reqwest::Client,
+    throttle: Arc<Throttle>,
+    translation: bool,
+}
+
+impl Netease {
+    pub fn new(client: reqwest::Client, throttle: Arc<Throttle>, translation: bool) -> Self {
+        Self {
+            client,
+            throttle,
+            translation,
+        }
+    }
+
+    async fn find_song(&self, data: &MusicData) -> Result<Option<Song>, QueryError> {
+        let terms = format!("{} {}", data.title, data.artist);
+        let request = self.client.get(NETEASE_SEARCH_URL).query(&[
+            ("s", terms.as_str()),
+            ("type", "1"),
+            ("limit", SEARCH_LIMIT),
+        ]);
+        let search = send_json::<SearchResponse>(request, &self.throttle).await?;
+        let title = fold(&data.title);
+        let artist = &data.keys.artist;
+        let duration = data.duration as i64 * 1000;
+        let song = search
+            .result
+            .map(|result| result.songs)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|song| fold(&song.name) == title)
+            .filter(|song| {
+                song.artists
+                    .iter()
+                    .any(|candidate| fold(&candidate.name) == **artist)
+            })
+            .filter(|song| (song.duration - duration).abs() <= DURATION_TOLERANCE_MS)
+            .min_by_key(|song| (song.duration - duration).abs());
+        Ok(song)
+    }
+
+    async fn lyrics(&self, id: u64) -> Result<LyricResponse, QueryError> {
+        let request = self.client.get(NETEASE_LYRIC_URL).query(&[
+            ("id", id.to_string().as_str()),
+            ("lv", "1"),
+            ("tv", "-1"),
+            ("rv", "-1"),
+        ]);
+        send_json::<LyricResponse>(request, &self.throttle).await
+    }
+
+    async fn query(&self, data: &MusicData) -> ProviderResult {
+        let Some(song) = self.find_song(data).await? else {
+            return Ok(Lyrics::None);
+        };
+        let response = self.lyrics(song.id).await?;
+        if response.pure_music {
+            return Ok(Lyrics::Instrumental);
+        }
+        let Some(lyric) = response
+            .lrc
+            .map(|lrc| lrc.lyric)
+            .filter(|lyric| !lyric.trim().is_empty())
+        else {
+            return Ok(Lyrics::None);
+        };
+        if lrc::validate(&lyric).timed_lines == 0 {
+            return Ok(Lyrics::Plain(lyric));
+        }
+        match response.tlyric.map(|tlyric| tlyric.lyric) {
+            Some(translation) if self.translation && !translation.trim().is_empty() => {
+                Ok(Lyrics::Synced(lrc::merge_translation(&lyric, &translation)))
+            }
+            _ => Ok(Lyrics::Synced(lyric)),
+        }
+    }
+
+    /// The original lyrics plus, when NetEase has them, the translated and romanized versions.
+    async fn list_variants(&self, data: &MusicData) -> Result<Vec<Candidate>, QueryError> {
+        let Some(song) = self.find_song(data).await? else {
+            return Ok(Vec::new());
+        };
+        let response = self.lyrics(song.id).await?;
+        let Some(original) = response
+            .lrc
+            .map(|lrc| lrc.lyric)
+            .filter(|lyric| !lyric.trim().is_empty())
+        else {
+            return Ok(Vec::new());
+        };
+        let candidate = |variant: &'static str, lyric: String| {
+            let synced = lrc::validate(&lyric).timed_lines > 0;
+            Candidate {
+                id: song.id,
+                track_name: song.name.clone(),
+                artist_name: song
+                    .artists
+                    .iter()
+                    .map(|artist| artist.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                album_name: song
+                    .album
+                    .as_ref()
+                    .map_or(String::new(), |album| album.name.clone()),
+                duration: song.duration as f64 / 1000.0,
+                instrumental: response.pure_music,
+                plain_lyrics: (!synced).then(|| lyric.clone()),
+                synced_lyrics: synced.then_some(lyric),
+                source: Some("netease"),
+                variant: Some(variant),
+            }
+        };
+        let mut variants = Vec::new();
+        let extras = [
+            ("translated", response.tlyric),
+            ("romanized", response.romalrc),
+        ];
+        for (variant, body) in extras {
+            if let Some(body) = body
+                && !body.lyric.trim().is_empty()
+            {
+                variants.push(candidate(
+                    variant,
+                    lrc::merge_translation(&original, &body.lyric),
+                ));
+            }
+        }
+        variants.insert(0, candidate("original", original));
+        Ok(variants)
+    }
+}
+
+impl LyricsProvider for Netease {
+    fn name(&self) -> &'static str {
+        "netease"
+    }
+
+    fn fetch<'a>(&'a self, data: &'a MusicData) -> BoxFuture<'a, ProviderResult> {
+        Box::pin(self.query(data))
+    }
+
+    fn variants<'a>(
+        &'a self,
+        data: &'a MusicData,
+    ) -> BoxFuture<'a, Result<Vec<Candidate>, QueryError>> {
+        Box::pin(self.list_variants(data))
+    }
+}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    result: Option<SearchResult>,
+}
+
+#[derive(Deserialize)]
+struct SearchResult {
+    #[serde(default)]
+    songs: Vec<Song>,
+}
+
+#[derive(Deserialize)]
+struct Song {
+    id: u64,
+    name: String,
+    #[serde(default)]
+    artists: Vec<Artist>,
+    #[serde(default)]
+    duration: i64,
+    album: Option<Album>,
+}
+
+#[derive(Deserialize)]
+struct Album {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Artist {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct LyricResponse {
+    #[serde(default, rename = "pureMusic")]
+    pure_music: bool,
+    lrc: Option<LyricBody>,
+    tlyric: Option<LyricBody>,
+    romalrc: Option<LyricBody>,
+}
+
+#[derive(Deserialize)]
+struct LyricBody {
+    #[serde(default)]
+    lyric: String,
+}
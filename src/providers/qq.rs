@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::Deserialize;
+
+use crate::lrc;
+use crate::musicdata::{Lyrics, MusicData, QueryError, fold};
+use crate::providers::{BoxFuture, LyricsProvider, ProviderResult, send_json};
+use crate::throttle::Throttle;
+
+pub const QQ_SEARCH_URL: &str = "https://c.y.qq.com/soso/fcgi-bin/client_search_cp";
+pub const QQ_LYRIC_URL: &str = "https://c.y.qq.com/lyric/fcgi-bin/fcg_query_lyric_new.fcg";
+/// The lyric endpoint rejects requests without a y.qq.com referer.
+const QQ_REFERER: &str = "https://y.qq.com/";
+const DURATION_TOLERANCE: i64 = 5;
+const SEARCH_LIMIT: &str = "10";
+
+pub struct QqMusic {
+    client: reqwest::Client,
+    throttle: Arc<Throttle>,
+}
+
+impl QqMusic {
+    pub fn new(client: reqwest::Client, throttle: Arc<Throttle>) -> Self {
+        Self { client, throttle }
+    }
+
+    async fn find_song(&self, data: &MusicData) -> Result<Option<String>, QueryError> {
+        let terms = format!("{} {}", data.title, data.artist);
+        let request = self.client.get(QQ_SEARCH_URL).query(&[
+            ("w", terms.as_str()),
+            ("format", "json"),
+            ("p", "1"),
+            ("n", SEARCH_LIMIT),
+        ]);
+        let search = send_json::<SearchResponse>(request, &self.throttle).await?;
+        let title = fold(&data.title);
+        let duration = data.duration as i64;
+        let song = search
+            .data
+            .song
+            .list
+            .into_iter()
+            .filter(|song| fold(&song.songname) == title)
+            .filter(|song| {
+                song.singer
+                    .iter()
+                    .any(|singer| fold(&singer.name) == *data.keys.artist)
+            })
+            .filter(|song| (song.interval - duration).abs() <= DURATION_TOLERANCE)
+            .min_by_key(|song| (song.interval - duration).abs());
+        Ok(song.map(|song| song.songmid))
+    }
+
+    async fn query(&self, data: &MusicData) -> ProviderResult {
+        let Some(mid) = self.find_song(data).await? else {
+            return Ok(Lyrics::None);
+        };
+        let request = self
+            .client
+            .get(QQ_LYRIC_URL)
+            .header(reqwest::header::REFERER, QQ_REFERER)
+            .query(&[
+                ("songmid", mid.as_str()),
+                ("format", "json"),
+                ("nobase64", "0"),
+            ]);
+        let response = send_json::<LyricResponse>(request, &self.throttle).await?;
+        let Some(lyric) = response.lyric.as_deref().and_then(decode_lyric) else {
+            return Ok(Lyrics::None);
+        };
+        if lyric.contains("此歌曲为没有填词的纯音乐") {
+            return Ok(Lyrics::Instrumental);
+        }
+        if lrc::validate(&lyric).timed_lines == 0 {
+            Ok(Lyrics::Plain(lyric))
+        } else {
+            Ok(Lyrics::Synced(lyric))
+        }
+    }
+}
+
+impl LyricsProvider for QqMusic {
+    fn name(&self) -> &'static str {
+        "qq"
+    }
+
+    fn fetch<'a>(&'a self, data: &'a MusicData) -> BoxFuture<'a, ProviderResult> {
+        Box::pin(self.query(data))
+    }
+}
+
+/// Lyrics come base64 encoded, with some punctuation escaped as HTML entities.
+fn decode_lyric(encoded: &str) -> Option<String> {
+    let bytes = STANDARD.decode(encoded.trim()).ok()?;
+    let lyric = String::from_utf8(bytes).ok()?;
+    let lyric = lyric
+        .replace("&apos;", "'")
+        .replace("&quot;", "\"")
+        .replace("&#32;", " ")
+        .replace("&#10;", "\n")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&");
+    (!lyric.trim().is_empty()).then_some(lyric)
+}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    data: SearchData,
+}
+
+#[derive(Deserialize)]
+struct SearchData {
+    song: SongList,
+}
+
+#[derive(Deserialize)]
+struct SongList {
+    #[serde(default)]
+    list: Vec<Song>,
+}
+
+#[derive(Deserialize)]
+struct Song {
+    songmid: String,
+    songname: String,
+    #[serde(default)]
+    singer: Vec<Singer>,
+    /// Length in seconds.
+    #[serde(default)]
+    interval: i64,
+}
+
+#[derive(Deserialize)]
+struct Singer {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct LyricResponse {
+    lyric: Option<String>,
+}
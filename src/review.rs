@@ -0,0 +1,81 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::musicdata::{MusicData, Scored};
+
+pub const REVIEW_FILE: &str = "review.ron";
+
+/// Tracks whose best search match scored under `auto_accept_score`, with the scored matches,
+/// waiting to be settled in the candidate picker instead of being written.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct Review(BTreeMap<PathBuf, Vec<Scored>>);
+
+impl Review {
+    pub fn add(&mut self, path: PathBuf, candidates: Vec<Scored>) {
+        self.0.insert(path, candidates);
+    }
+
+    pub fn remove(&mut self, path: &Path) -> bool {
+        self.0.remove(path).is_some()
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&[Scored]> {
+        self.0.get(path).map(Vec::as_slice)
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.0.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Every queued track found in `music`, ready to be triaged elsewhere.
+    pub fn export(&self, music: &[Arc<MusicData>]) -> Vec<ReviewEntry> {
+        music
+            .iter()
+            .filter_map(|data| {
+                let candidates = self.0.get(&data.path)?;
+                Some(ReviewEntry {
+                    path: data.path.clone(),
+                    title: data.title.clone(),
+                    artist: data.artist.to_string(),
+                    album: data.album.to_string(),
+                    duration: data.duration,
+                    candidates: candidates.clone(),
+                    decision: None,
+                })
+            })
+            .collect()
+    }
+}
+
+/// A queued track as exported for triage. Setting `decision` and importing the file back
+/// saves or drops the track's matches.
+#[derive(Serialize, Deserialize)]
+pub struct ReviewEntry {
+    pub path: PathBuf,
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub duration: usize,
+    pub candidates: Vec<Scored>,
+    #[serde(default)]
+    pub decision: Option<Decision>,
+}
+
+/// Written as `{"accept": <candidate id>}` or `"reject"`.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    Accept(u64),
+    Reject,
+}
@@ -0,0 +1,761 @@
+use std::{
+    collections::HashMap,
+    env::{self, current_dir, home_dir},
+    hash::{BuildHasher, Hasher, RandomState},
+    path::{PathBuf, absolute},
+    time::Duration,
+};
+
+use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::library::MUSIC_EXTENSIONS;
+use crate::musicdata::{LRCLIB_URL, LyricsStatus, MusicData};
+use crate::throttle::Politeness;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LayoutMode {
+    #[default]
+    Split,
+    Compact,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StatusIcons {
+    #[default]
+    Text,
+    Ascii,
+    NerdFont,
+}
+
+impl StatusIcons {
+    pub fn label(&self, status: Option<LyricsStatus>) -> &'static str {
+        match (self, status) {
+            (StatusIcons::Text, None) => "?",
+            (StatusIcons::Text, Some(LyricsStatus::None)) => "none",
+            (StatusIcons::Text, Some(LyricsStatus::Synced)) => "synced",
+            (StatusIcons::Text, Some(LyricsStatus::Plain)) => "plain",
+            (StatusIcons::Text, Some(LyricsStatus::Instrumental)) => "instr",
+            (StatusIcons::Ascii, None) => "?",
+            (StatusIcons::Ascii, Some(LyricsStatus::None)) => "-",
+            (StatusIcons::Ascii, Some(LyricsStatus::Synced)) => "S",
+            (StatusIcons::Ascii, Some(LyricsStatus::Plain)) => "P",
+            (StatusIcons::Ascii, Some(LyricsStatus::Instrumental)) => "I",
+            (StatusIcons::NerdFont, None) => "\u{f128}",
+            (StatusIcons::NerdFont, Some(LyricsStatus::None)) => "\u{f00d}",
+            (StatusIcons::NerdFont, Some(LyricsStatus::Synced)) => "\u{f075a}",
+            (StatusIcons::NerdFont, Some(LyricsStatus::Plain)) => "\u{f15c}",
+            (StatusIcons::NerdFont, Some(LyricsStatus::Instrumental)) => "\u{f0388}",
+        }
+    }
+
+    pub fn width(&self) -> u16 {
+        match self {
+            StatusIcons::Text => 6,
+            StatusIcons::Ascii => 1,
+            // Nerd font glyphs are often drawn double width.
+            StatusIcons::NerdFont => 2,
+        }
+    }
+}
+
+/// The accessible presets never rely on hue alone: every status also differs in weight or
+/// decoration.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Theme {
+    #[default]
+    Default,
+    HighContrast,
+    ColorblindSafe,
+}
+
+impl Theme {
+    pub fn highlight(&self) -> Style {
+        match self {
+            Theme::Default => Style::new().bg(Color::White).fg(Color::Black),
+            Theme::HighContrast => Style::new()
+                .bg(Color::Yellow)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+            Theme::ColorblindSafe => Style::new().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+        }
+    }
+
+    pub fn marked(&self) -> Style {
+        match self {
+            Theme::Default => Style::new().fg(Color::Yellow),
+            Theme::HighContrast => Style::new()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            // Okabe-Ito reddish purple.
+            Theme::ColorblindSafe => Style::new()
+                .fg(Color::Rgb(204, 121, 167))
+                .add_modifier(Modifier::UNDERLINED),
+        }
+    }
+
+    pub fn timestamp(&self) -> Style {
+        match self {
+            Theme::Default => Style::new().fg(Color::DarkGray),
+            Theme::HighContrast => Style::new().fg(Color::Yellow),
+            // Okabe-Ito sky blue.
+            Theme::ColorblindSafe => Style::new()
+                .fg(Color::Rgb(86, 180, 233))
+                .add_modifier(Modifier::ITALIC),
+        }
+    }
+
+    pub fn status(&self, status: Option<LyricsStatus>) -> Style {
+        // Colors are from the Okabe-Ito palette.
+        match (self, status) {
+            (Theme::Default, _) | (_, None) => Style::new(),
+            (Theme::HighContrast, Some(LyricsStatus::Synced)) => {
+                Style::new().fg(Color::White).add_modifier(Modifier::BOLD)
+            }
+            (Theme::HighContrast, Some(LyricsStatus::Plain)) => Style::new().fg(Color::White),
+            (Theme::HighContrast, Some(LyricsStatus::Instrumental)) => {
+                Style::new().fg(Color::White).add_modifier(Modifier::ITALIC)
+            }
+            (Theme::HighContrast, Some(LyricsStatus::None)) => {
+                Style::new().add_modifier(Modifier::REVERSED)
+            }
+            (Theme::ColorblindSafe, Some(LyricsStatus::Synced)) => Style::new()
+                .fg(Color::Rgb(0, 114, 178))
+                .add_modifier(Modifier::BOLD),
+            (Theme::ColorblindSafe, Some(LyricsStatus::Plain)) => {
+                Style::new().fg(Color::Rgb(230, 159, 0))
+            }
+            (Theme::ColorblindSafe, Some(LyricsStatus::Instrumental)) => Style::new()
+                .fg(Color::Rgb(86, 180, 233))
+                .add_modifier(Modifier::ITALIC),
+            (Theme::ColorblindSafe, Some(LyricsStatus::None)) => Style::new()
+                .fg(Color::Rgb(213, 94, 0))
+                .add_modifier(Modifier::REVERSED),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LyricsOutput {
+    #[default]
+    Sidecar,
+    Embedded,
+    Both,
+}
+
+impl LyricsOutput {
+    pub fn sidecar(&self) -> bool {
+        matches!(self, LyricsOutput::Sidecar | LyricsOutput::Both)
+    }
+
+    pub fn embedded(&self) -> bool {
+        matches!(self, LyricsOutput::Embedded | LyricsOutput::Both)
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            LyricsOutput::Sidecar => LyricsOutput::Embedded,
+            LyricsOutput::Embedded => LyricsOutput::Both,
+            LyricsOutput::Both => LyricsOutput::Sidecar,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampPrecision {
+    /// Write timestamps exactly as the provider returned them.
+    #[default]
+    Keep,
+    Centiseconds,
+    Milliseconds,
+}
+
+/// Everything needed to write lyrics, detached from `Settings` so it can move into tasks.
+#[derive(Debug, Clone)]
+pub struct WriteOptions {
+    pub output: LyricsOutput,
+    pub precision: TimestampPrecision,
+    pub notify: PlayerNotify,
+    pub min_lines: usize,
+    pub max_bytes: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PlayerNotify {
+    /// Bump the audio file's modification time after its lyrics change.
+    #[serde(default)]
+    pub touch_audio: bool,
+    /// Command run after each write; `{audio}` and `{lyrics}` are replaced with the paths.
+    #[serde(default)]
+    pub hook: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProviderOverride {
+    #[serde(default)]
+    pub artist: Option<String>,
+    #[serde(default)]
+    pub path: Option<String>,
+    pub providers: Vec<String>,
+}
+
+impl ProviderOverride {
+    fn matches(&self, data: &MusicData) -> bool {
+        if self.artist.is_none() && self.path.is_none() {
+            return false;
+        }
+        let artist = self.artist.as_deref().is_none_or(|pattern| {
+            glob_matches(&pattern.to_lowercase(), &data.keys.artist)
+                || glob_matches(&pattern.to_lowercase(), data.group_key())
+        });
+        let path = self
+            .path
+            .as_deref()
+            .is_none_or(|pattern| glob_matches(pattern, &data.path.to_string_lossy()));
+        artist && path
+    }
+}
+
+/// Matches `*` (any run of characters) and `?` (one character).
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RefreshSchedule {
+    #[serde(default = "default_refresh_interval")]
+    pub interval_minutes: u64,
+    #[serde(default = "default_refresh_cooldown")]
+    pub cooldown_hours: u64,
+    #[serde(default = "default_refresh_max_tracks")]
+    pub max_tracks: usize,
+}
+
+impl Default for RefreshSchedule {
+    fn default() -> Self {
+        Self {
+            interval_minutes: default_refresh_interval(),
+            cooldown_hours: default_refresh_cooldown(),
+            max_tracks: default_refresh_max_tracks(),
+        }
+    }
+}
+
+/// How often a provider is retried after a transient failure before the track is given up on.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RetryPolicy {
+    #[serde(default = "default_retry_attempts")]
+    pub attempts: u32,
+    #[serde(default = "default_retry_base_delay")]
+    pub base_delay_ms: u64,
+    #[serde(default = "default_retry_max_delay")]
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: default_retry_attempts(),
+            base_delay_ms: default_retry_base_delay(),
+            max_delay_ms: default_retry_max_delay(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential delay before retry number `retry` (starting at 0), with full jitter.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let ceiling = self
+            .base_delay_ms
+            .saturating_mul(1u64.checked_shl(retry).unwrap_or(u64::MAX))
+            .min(self.max_delay_ms);
+        let random = RandomState::new().build_hasher().finish();
+        Duration::from_millis(random % ceiling.saturating_add(1))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Settings {
+    #[serde(default = "default_concurrent")]
+    pub concurrent_queries: usize,
+    #[serde(default = "default_music_path")]
+    pub music_path: PathBuf,
+    #[serde(default)]
+    pub layout: LayoutMode,
+    #[serde(default = "default_concurrent_local")]
+    pub concurrent_local_reads: usize,
+    #[serde(default)]
+    pub contact: Option<String>,
+    #[serde(default)]
+    pub politeness: HashMap<String, Politeness>,
+    #[serde(default = "default_min_duration")]
+    pub min_duration: usize,
+    #[serde(default)]
+    pub status_icons: StatusIcons,
+    #[serde(default = "default_providers")]
+    pub providers: Vec<String>,
+    #[serde(default)]
+    pub provider_overrides: Vec<ProviderOverride>,
+    #[serde(default)]
+    pub refresh: RefreshSchedule,
+    #[serde(default = "default_music_extensions")]
+    pub music_extensions: Vec<String>,
+    #[serde(default)]
+    pub sniff_extensionless: bool,
+    #[serde(default)]
+    pub lyrics_output: LyricsOutput,
+    /// Keep lyrics fetched in the TUI in memory until they are saved with `w`/`W`.
+    #[serde(default)]
+    pub preview_only: bool,
+    #[serde(default)]
+    pub screen_reader: bool,
+    #[serde(default)]
+    pub theme: Theme,
+    #[serde(default)]
+    pub netease_translation: bool,
+    #[serde(default)]
+    pub player_notify: PlayerNotify,
+    #[serde(default)]
+    pub musixmatch_api_key: Option<String>,
+    #[serde(default)]
+    pub timestamp_precision: TimestampPrecision,
+    /// Base URL of the lrclib instance, for self-hosted servers.
+    #[serde(default = "default_lrclib_url")]
+    pub lrclib_url: String,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout_secs: u64,
+    /// Longest a response may go without sending data before the request is abandoned.
+    #[serde(default = "default_read_timeout")]
+    pub read_timeout_secs: u64,
+    /// Proxy for every request, e.g. `socks5h://127.0.0.1:9050` for Tor. When unset,
+    /// `HTTP_PROXY`, `HTTPS_PROXY` and `ALL_PROXY` from the environment are used.
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// Pick up files changed in other windows whenever the terminal regains focus.
+    #[serde(default)]
+    pub rescan_on_focus: bool,
+    /// Show a desktop notification summing up each fetch batch when it finishes.
+    #[serde(default)]
+    pub notify_on_finish: bool,
+    /// Start from the library index alone, reading a top-level folder's tags only once it is
+    /// opened in the folders view or matched by the artist filter.
+    #[serde(default)]
+    pub lazy_scan: bool,
+    /// How long lrclib results, misses included, are reused before asking again; 0 disables.
+    #[serde(default = "default_response_cache_hours")]
+    pub response_cache_hours: u64,
+    /// Tracks no provider had lyrics for are skipped by bulk fetches for this long.
+    #[serde(default = "default_miss_cooldown_hours")]
+    pub miss_cooldown_hours: u64,
+    /// Search matches scoring below this are kept for review instead of being saved; 0 saves
+    /// every match that clears the minimums.
+    #[serde(default = "default_auto_accept_score")]
+    pub auto_accept_score: f64,
+    /// Lyrics with fewer lines of text than this are refused as a provider glitch; 0 disables.
+    #[serde(default = "default_min_lyrics_lines")]
+    pub min_lyrics_lines: usize,
+    /// Lyrics larger than this many bytes are refused as a provider glitch; 0 disables.
+    #[serde(default = "default_max_lyrics_bytes")]
+    pub max_lyrics_bytes: usize,
+}
+
+impl Settings {
+    pub fn politeness_for(&self, provider: &str, endpoint: &str) -> Politeness {
+        self.politeness
+            .get(provider)
+            .cloned()
+            .unwrap_or_else(|| Politeness::for_endpoint(endpoint))
+    }
+
+    pub fn write_options(&self) -> WriteOptions {
+        WriteOptions {
+            output: self.lyrics_output,
+            precision: self.timestamp_precision,
+            notify: self.player_notify.clone(),
+            min_lines: self.min_lyrics_lines,
+            max_bytes: self.max_lyrics_bytes,
+        }
+    }
+
+    pub fn providers_for(&self, data: &MusicData) -> &[String] {
+        self.provider_overrides
+            .iter()
+            .find(|provider_override| provider_override.matches(data))
+            .map_or(&self.providers, |provider_override| {
+                &provider_override.providers
+            })
+    }
+}
+
+pub fn user_agent(contact: Option<&str>) -> String {
+    let mut agent = format!(
+        "lrcfetch/{} (https://github.com/hagaraShin/lrcfetch-tui",
+        env!("CARGO_PKG_VERSION")
+    );
+    if let Some(contact) = contact {
+        agent.push_str("; ");
+        agent.push_str(contact);
+    }
+    agent.push(')');
+    agent
+}
+
+pub fn build_client(settings: &Settings) -> reqwest::Client {
+    let mut builder = reqwest::ClientBuilder::new()
+        .user_agent(user_agent(settings.contact.as_deref()))
+        .connect_timeout(Duration::from_secs(settings.connect_timeout_secs))
+        .read_timeout(Duration::from_secs(settings.read_timeout_secs));
+    if let Some(proxy) = &settings.proxy {
+        let proxy =
+            reqwest::Proxy::all(proxy).expect("proxy checked when loading the configuration");
+        builder = builder.proxy(proxy);
+    }
+    builder.build().unwrap()
+}
+
+fn default_lrclib_url() -> String {
+    LRCLIB_URL.to_string()
+}
+
+fn default_response_cache_hours() -> u64 {
+    24
+}
+
+fn default_miss_cooldown_hours() -> u64 {
+    24
+}
+
+fn default_auto_accept_score() -> f64 {
+    0.9
+}
+
+fn default_min_lyrics_lines() -> usize {
+    2
+}
+
+fn default_max_lyrics_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_connect_timeout() -> u64 {
+    10
+}
+
+fn default_read_timeout() -> u64 {
+    30
+}
+
+fn default_retry_attempts() -> u32 {
+    3
+}
+
+fn default_retry_base_delay() -> u64 {
+    500
+}
+
+fn default_retry_max_delay() -> u64 {
+    10_000
+}
+
+fn default_refresh_interval() -> u64 {
+    6 * 60
+}
+
+fn default_refresh_cooldown() -> u64 {
+    7 * 24
+}
+
+fn default_refresh_max_tracks() -> usize {
+    200
+}
+
+fn default_music_extensions() -> Vec<String> {
+    MUSIC_EXTENSIONS.map(str::to_string).to_vec()
+}
+
+fn default_providers() -> Vec<String> {
+    vec!["lrclib".to_string()]
+}
+
+fn default_concurrent() -> usize {
+    50
+}
+
+fn default_min_duration() -> usize {
+    30
+}
+
+fn default_concurrent_local() -> usize {
+    64
+}
+
+fn default_music_path() -> PathBuf {
+    if let Ok(Ok(path)) = std::env::var("XDG_MUSIC_DIR").map(absolute) {
+        path
+    } else if let Ok(Ok(path)) =
+        std::env::var("HOME").map(|path| absolute(path).map(|path| path.join("Music")))
+    {
+        path
+    } else if let Ok(path) = current_dir() {
+        path
+    } else {
+        PathBuf::from(".")
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            concurrent_queries: 50,
+            music_path: default_music_path(),
+            layout: LayoutMode::default(),
+            concurrent_local_reads: default_concurrent_local(),
+            contact: None,
+            politeness: HashMap::new(),
+            min_duration: default_min_duration(),
+            status_icons: StatusIcons::default(),
+            providers: default_providers(),
+            provider_overrides: Vec::new(),
+            refresh: RefreshSchedule::default(),
+            music_extensions: default_music_extensions(),
+            sniff_extensionless: false,
+            lyrics_output: LyricsOutput::default(),
+            preview_only: false,
+            screen_reader: false,
+            theme: Theme::default(),
+            netease_translation: false,
+            player_notify: PlayerNotify::default(),
+            musixmatch_api_key: None,
+            timestamp_precision: TimestampPrecision::default(),
+            lrclib_url: default_lrclib_url(),
+            retry: RetryPolicy::default(),
+            connect_timeout_secs: default_connect_timeout(),
+            read_timeout_secs: default_read_timeout(),
+            proxy: None,
+            rescan_on_focus: false,
+            notify_on_finish: false,
+            lazy_scan: false,
+            response_cache_hours: default_response_cache_hours(),
+            miss_cooldown_hours: default_miss_cooldown_hours(),
+            auto_accept_score: default_auto_accept_score(),
+            min_lyrics_lines: default_min_lyrics_lines(),
+            max_lyrics_bytes: default_max_lyrics_bytes(),
+        }
+    }
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    if let Ok(xdg_config_home) = env::var("XDG_CONFIG_HOME")
+        && let Ok(mut path) = absolute(xdg_config_home)
+    {
+        path.push("lrcfetch");
+        path.push("config.ron");
+        if path.exists() {
+            return Some(path);
+        }
+    };
+    if let Some(mut home) = home_dir() {
+        home.push(".config");
+        home.push("lrcfetch");
+        home.push("config.ron");
+        if home.exists() {
+            return Some(home);
+        };
+    }
+    if let Ok(mut pwd) = current_dir() {
+        pwd.push("config.ron");
+        if pwd.exists() {
+            return Some(pwd);
+        };
+    }
+    None
+}
+
+fn default_future_config_path() -> Option<PathBuf> {
+    if let Ok(xdg_config_home) = env::var("XDG_CONFIG_HOME")
+        && let Ok(mut path) = absolute(xdg_config_home)
+    {
+        path.push("lrcfetch");
+        path.push("config.ron");
+        return Some(path);
+    };
+    if let Some(mut home) = home_dir() {
+        home.push(".config");
+        home.push("lrcfetch");
+        home.push("config.ron");
+        return Some(home);
+    }
+    if let Ok(mut pwd) = current_dir() {
+        pwd.push("config.ron");
+        return Some(pwd);
+    }
+    None
+}
+
+pub async fn get_or_create_config() -> Option<Settings> {
+    if let Some(config_path) = default_config_path() {
+        let config_file = tokio::fs::read_to_string(config_path)
+            .await
+            .unwrap_or_default();
+        let settings = ron::from_str::<Settings>(config_file.as_str()).ok()?;
+        if let Some(proxy) = &settings.proxy
+            && let Err(err) = reqwest::Proxy::all(proxy)
+        {
+            // Going direct instead would leak traffic the user meant to route elsewhere.
+            eprintln!("lrcfetch: invalid proxy {proxy}: {err}");
+            return None;
+        }
+        return Some(settings);
+    }
+    let settings = Settings::default();
+    let Some(path) = default_future_config_path() else {
+        return Some(settings);
+    };
+    if let Some(parent) = path.parent() {
+        let Ok(()) = tokio::fs::create_dir_all(parent).await else {
+            return Some(settings);
+        };
+    } else {
+        return Some(settings);
+    };
+    let Ok(mut file) = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .await
+    else {
+        return Some(settings);
+    };
+    let ron = ron::to_string(&settings).unwrap();
+    file.write_all(ron.as_bytes()).await.unwrap();
+    Some(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_stays_under_the_ceiling() {
+        let policy = RetryPolicy {
+            attempts: 3,
+            base_delay_ms: 500,
+            max_delay_ms: 2000,
+        };
+        for retry in 0..70 {
+            let ceiling = (500u64 << retry.min(2)).min(2000);
+            assert!(policy.delay(retry) <= Duration::from_millis(ceiling));
+        }
+    }
+
+    #[test]
+    fn retry_delay_without_a_ceiling() {
+        let policy = RetryPolicy {
+            attempts: 3,
+            base_delay_ms: u64::MAX,
+            max_delay_ms: u64::MAX,
+        };
+        for retry in [0, 1, 63, 64, u32::MAX] {
+            policy.delay(retry);
+        }
+    }
+
+    #[test]
+    fn glob_wildcards() {
+        let cases = [
+            ("", "", true),
+            ("", "a", false),
+            ("*", "", true),
+            ("*", "anything", true),
+            ("a?c", "abc", true),
+            ("a?c", "ac", false),
+            ("a?c", "abbc", false),
+            ("?", "", false),
+            ("*.flac", "/music/x.flac", true),
+            ("*.flac", "/music/x.flac.part", false),
+            ("a**b", "ab", true),
+        ];
+        for (pattern, text, expected) in cases {
+            assert_eq!(glob_matches(pattern, text), expected, "{pattern} vs {text}");
+        }
+    }
+
+    #[test]
+    fn glob_trailing_star() {
+        let cases = [
+            ("/music/Anime/*", "/music/Anime/Show/01.flac", true),
+            ("/music/Anime/*", "/music/Anime/", true),
+            ("/music/Anime/*", "/music/Other/01.flac", false),
+            ("abc*", "abc", true),
+            ("abc*", "ab", false),
+        ];
+        for (pattern, text, expected) in cases {
+            assert_eq!(glob_matches(pattern, text), expected, "{pattern} vs {text}");
+        }
+    }
+
+    #[test]
+    fn glob_backtracks() {
+        let cases = [
+            ("*a*b", "xaxb", true),
+            ("*a*b", "xaxbx", false),
+            ("*a*b", "xbxa", false),
+            ("a*b*c", "aXbYbZc", true),
+            ("*ab", "aab", true),
+            ("*aab", "aaab", true),
+        ];
+        for (pattern, text, expected) in cases {
+            assert_eq!(glob_matches(pattern, text), expected, "{pattern} vs {text}");
+        }
+    }
+
+    #[test]
+    fn glob_non_ascii() {
+        let cases = [
+            (
+                "*/宇多田ヒカル/*",
+                "/music/宇多田ヒカル/First Love.flac",
+                true,
+            ),
+            (
+                "*/宇多田ヒカル/*",
+                "/music/椎名林檎/丸の内サディスティック.flac",
+                false,
+            ),
+            ("bj?rk", "björk", true),
+            ("sigur r?s", "sigur rós", true),
+            ("??", "ö", false),
+        ];
+        for (pattern, text, expected) in cases {
+            assert_eq!(glob_matches(pattern, text), expected, "{pattern} vs {text}");
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::{
+    collections::HashMap,
+    env::{self, home_dir},
+    path::{PathBuf, absolute},
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+pub fn data_dir() -> Option<PathBuf> {
+    if let Ok(xdg_data_home) = env::var("XDG_DATA_HOME")
+        && let Ok(mut path) = absolute(xdg_data_home)
+    {
+        path.push("lrcfetch");
+        return Some(path);
+    };
+    let mut home = home_dir()?;
+    home.push(".local");
+    home.push("share");
+    home.push("lrcfetch");
+    Some(home)
+}
+
+pub async fn load<T: DeserializeOwned + Default>(name: &str) -> T {
+    let Some(path) = data_dir().map(|dir| dir.join(name)) else {
+        return T::default();
+    };
+    let Ok(contents) = tokio::fs::read_to_string(path).await else {
+        return T::default();
+    };
+    ron::from_str(&contents).unwrap_or_default()
+}
+
+/// One lock per file name, so two saves of the same file never share its `.part`.
+static SAVING: LazyLock<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>> =
+    LazyLock::new(Mutex::default);
+
+pub async fn save<T: Serialize>(name: &str, value: &T) -> Result<(), tokio::io::Error> {
+    let Some(dir) = data_dir() else {
+        return Ok(());
+    };
+    let contents = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(tokio::io::Error::other)?;
+    let lock = SAVING
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .entry(name.to_string())
+        .or_default()
+        .clone();
+    let _saving = lock.lock().await;
+    tokio::fs::create_dir_all(&dir).await?;
+    // Write to a temporary name and rename, so an interrupted save leaves the old file intact.
+    let path = dir.join(name);
+    let mut partial = path.clone().into_os_string();
+    partial.push(".part");
+    tokio::fs::write(&partial, contents).await?;
+    tokio::fs::rename(&partial, &path).await
+}
@@ -0,0 +1,303 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Politeness {
+    pub min_delay_ms: u64,
+    pub max_burst: u32,
+    pub backoff_multiplier: f64,
+}
+
+impl Politeness {
+    pub fn public() -> Self {
+        Self {
+            min_delay_ms: 250,
+            max_burst: 4,
+            backoff_multiplier: 2.0,
+        }
+    }
+
+    pub fn local() -> Self {
+        Self {
+            min_delay_ms: 0,
+            max_burst: 64,
+            backoff_multiplier: 1.5,
+        }
+    }
+
+    pub fn for_endpoint(url: &str) -> Self {
+        let host = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string));
+        match host.as_deref() {
+            Some("localhost" | "127.0.0.1" | "[::1]") => Self::local(),
+            _ => Self::public(),
+        }
+    }
+}
+
+pub struct Throttle {
+    politeness: Politeness,
+    state: Mutex<ThrottleState>,
+    /// Requests let through so far.
+    requests: AtomicUsize,
+}
+
+struct ThrottleState {
+    tokens: f64,
+    last_refill: Instant,
+    backoff: Duration,
+    blocked_until: Instant,
+    rate_limits: VecDeque<Instant>,
+}
+
+pub struct ThrottleStats {
+    pub tokens: f64,
+    pub capacity: u32,
+    pub backoff: Duration,
+    pub blocked_for: Duration,
+    pub recent_rate_limits: usize,
+}
+
+impl Throttle {
+    pub fn new(politeness: Politeness) -> Self {
+        let now = Instant::now();
+        Self {
+            state: Mutex::new(ThrottleState {
+                tokens: politeness.max_burst.max(1) as f64,
+                last_refill: now,
+                backoff: Duration::ZERO,
+                blocked_until: now,
+                rate_limits: VecDeque::new(),
+            }),
+            requests: AtomicUsize::new(0),
+            politeness,
+        }
+    }
+
+    fn min_delay(&self) -> Duration {
+        Duration::from_millis(self.politeness.min_delay_ms)
+    }
+
+    pub async fn wait(&self) {
+        loop {
+            let sleep = {
+                let mut state = self.state.lock().unwrap();
+                let now = Instant::now();
+                let capacity = self.politeness.max_burst.max(1) as f64;
+                if self.politeness.min_delay_ms == 0 {
+                    state.tokens = capacity;
+                } else {
+                    let refilled = now.duration_since(state.last_refill).as_secs_f64()
+                        / self.min_delay().as_secs_f64();
+                    state.tokens = (state.tokens + refilled).min(capacity);
+                }
+                state.last_refill = now;
+                if now < state.blocked_until {
+                    state.blocked_until - now
+                } else if state.tokens >= 1.0 {
+                    state.tokens -= 1.0;
+                    self.requests.fetch_add(1, Ordering::Relaxed);
+                    return;
+                } else {
+                    self.min_delay().mul_f64(1.0 - state.tokens)
+                }
+            };
+            tokio::time::sleep(sleep).await;
+        }
+    }
+
+    pub fn requests(&self) -> usize {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    pub fn penalize(&self) {
+        let mut state = self.state.lock().unwrap();
+        let base = state
+            .backoff
+            .max(self.min_delay())
+            .max(Duration::from_millis(100));
+        state.backoff = base
+            .mul_f64(self.politeness.backoff_multiplier.max(1.0))
+            .min(MAX_BACKOFF);
+        state.blocked_until = Instant::now() + state.backoff;
+    }
+
+    /// Backs off after a 429, waiting at least as long as the server's `Retry-After` asks.
+    pub fn rate_limited(&self, retry_after: Option<Duration>) {
+        self.penalize();
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        if let Some(retry_after) = retry_after {
+            state.blocked_until = state
+                .blocked_until
+                .max(now + retry_after.min(MAX_RETRY_AFTER));
+        }
+        state.rate_limits.push_back(now);
+    }
+
+    /// Halves `limit` for every rate limit in the last minute, so a burst of 429s shrinks
+    /// the number of requests in flight until the server calms down.
+    pub fn concurrency(&self, limit: usize) -> usize {
+        let recent = self.stats().recent_rate_limits;
+        limit
+            .checked_shr(recent.try_into().unwrap_or(u32::MAX))
+            .unwrap_or(0)
+            .max(1)
+    }
+
+    pub fn stats(&self) -> ThrottleStats {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        while state
+            .rate_limits
+            .front()
+            .is_some_and(|at| now.duration_since(*at) > RATE_LIMIT_WINDOW)
+        {
+            state.rate_limits.pop_front();
+        }
+        let capacity = self.politeness.max_burst.max(1);
+        let tokens = if self.politeness.min_delay_ms == 0 {
+            capacity as f64
+        } else {
+            let refilled = now.duration_since(state.last_refill).as_secs_f64()
+                / self.min_delay().as_secs_f64();
+            (state.tokens + refilled).min(capacity as f64)
+        };
+        ThrottleStats {
+            tokens,
+            capacity,
+            backoff: state.backoff,
+            blocked_for: state.blocked_until.saturating_duration_since(now),
+            recent_rate_limits: state.rate_limits.len(),
+        }
+    }
+
+    pub fn reward(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.backoff.is_zero() {
+            return;
+        }
+        state.backoff = state
+            .backoff
+            .div_f64(self.politeness.backoff_multiplier.max(1.0));
+        if state.backoff <= self.min_delay() {
+            state.backoff = Duration::ZERO;
+        }
+    }
+}
+
+/// The delay a rate-limited response asks for, when `Retry-After` is given in seconds.
+pub fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let header = response.headers().get(reqwest::header::RETRY_AFTER)?;
+    let seconds = header.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn politeness(min_delay_ms: u64, max_burst: u32) -> Politeness {
+        Politeness {
+            min_delay_ms,
+            max_burst,
+            backoff_multiplier: 2.0,
+        }
+    }
+
+    #[tokio::test]
+    async fn burst_then_waits() {
+        let throttle = Throttle::new(politeness(60_000, 3));
+        for _ in 0..3 {
+            throttle.wait().await;
+        }
+        assert_eq!(throttle.requests(), 3);
+        assert!(throttle.stats().tokens < 1.0);
+        let next = tokio::time::timeout(Duration::from_millis(50), throttle.wait()).await;
+        assert!(next.is_err());
+        assert_eq!(throttle.requests(), 3);
+    }
+
+    #[tokio::test]
+    async fn no_delay_never_runs_dry() {
+        let throttle = Throttle::new(politeness(0, 2));
+        for _ in 0..100 {
+            throttle.wait().await;
+        }
+        assert_eq!(throttle.stats().tokens, 2.0);
+    }
+
+    #[test]
+    fn backoff_grows_to_the_cap() {
+        let throttle = Throttle::new(politeness(250, 4));
+        let mut expected = Duration::from_millis(250);
+        for _ in 0..6 {
+            throttle.penalize();
+            expected *= 2;
+            assert_eq!(throttle.stats().backoff, expected);
+        }
+        for _ in 0..20 {
+            throttle.penalize();
+        }
+        assert_eq!(throttle.stats().backoff, MAX_BACKOFF);
+        assert!(throttle.stats().blocked_for <= MAX_BACKOFF);
+    }
+
+    #[test]
+    fn backoff_decays_to_zero() {
+        let throttle = Throttle::new(politeness(250, 4));
+        for _ in 0..3 {
+            throttle.penalize();
+        }
+        assert_eq!(throttle.stats().backoff, Duration::from_millis(2000));
+        throttle.reward();
+        assert_eq!(throttle.stats().backoff, Duration::from_millis(1000));
+        throttle.reward();
+        assert_eq!(throttle.stats().backoff, Duration::from_millis(500));
+        throttle.reward();
+        assert_eq!(throttle.stats().backoff, Duration::ZERO);
+        throttle.reward();
+        assert_eq!(throttle.stats().backoff, Duration::ZERO);
+    }
+
+    #[test]
+    fn retry_after_is_honoured_up_to_a_limit() {
+        let throttle = Throttle::new(politeness(250, 4));
+        throttle.rate_limited(Some(Duration::from_secs(120)));
+        let blocked = throttle.stats().blocked_for;
+        assert!(blocked > Duration::from_secs(110) && blocked <= Duration::from_secs(120));
+        throttle.rate_limited(Some(Duration::from_secs(3600)));
+        assert!(throttle.stats().blocked_for <= MAX_RETRY_AFTER);
+        assert!(throttle.stats().blocked_for > Duration::from_secs(290));
+    }
+
+    #[test]
+    fn concurrency_halves_per_rate_limit() {
+        let throttle = Throttle::new(politeness(250, 4));
+        assert_eq!(throttle.concurrency(16), 16);
+        let mut expected = 16;
+        for _ in 0..4 {
+            throttle.rate_limited(None);
+            expected /= 2;
+            assert_eq!(throttle.concurrency(16), expected);
+        }
+        for _ in 0..100 {
+            throttle.rate_limited(None);
+        }
+        assert_eq!(throttle.concurrency(16), 1);
+        assert_eq!(throttle.stats().recent_rate_limits, 104);
+    }
+}
@@ -0,0 +1,294 @@
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryTerms {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+}
+
+const TITLE_MARKERS: [&str; 5] = ["feat.", "feat ", "ft.", "featuring ", "with "];
+const ARTIST_SEPARATORS: [&str; 7] = [
+    " feat. ",
+    " feat ",
+    " ft. ",
+    " featuring ",
+    " with ",
+    " & ",
+    ", ",
+];
+
+fn split_guest_in_title(title: &str) -> Option<(String, String)> {
+    let lower = title.to_ascii_lowercase();
+    for (open, close) in [('(', ')'), ('[', ']')] {
+        for marker in TITLE_MARKERS {
+            let pattern = format!("{open}{marker}");
+            let Some(start) = lower.find(&pattern) else {
+                continue;
+            };
+            let guests_start = start + pattern.len();
+            let end = lower[guests_start..]
+                .find(close)
+                .map(|end| guests_start + end)
+                .unwrap_or(title.len());
+            let guests = title[guests_start..end].trim();
+            let base = format!("{}{}", &title[..start], title.get(end + 1..).unwrap_or(""));
+            if !guests.is_empty() {
+                return Some((base.trim().to_string(), guests.to_string()));
+            }
+        }
+    }
+    for marker in [" feat. ", " ft. ", " featuring "] {
+        if let Some(start) = lower.find(marker) {
+            let guests = title[start + marker.len()..].trim();
+            if !guests.is_empty() {
+                return Some((title[..start].trim().to_string(), guests.to_string()));
+            }
+        }
+    }
+    None
+}
+
+fn split_artists(artist: &str) -> Option<(String, String)> {
+    let lower = artist.to_ascii_lowercase();
+    ARTIST_SEPARATORS
+        .iter()
+        .filter_map(|separator| lower.find(separator).map(|index| (index, separator.len())))
+        .min_by_key(|(index, _)| *index)
+        .map(|(index, len)| {
+            (
+                artist[..index].trim().to_string(),
+                artist[index + len..].trim().to_string(),
+            )
+        })
+        .filter(|(primary, guests)| !primary.is_empty() && !guests.is_empty())
+}
+
+/// Builds the retry ladder for a track, starting with the tags as-is and
+/// moving guest artists between the title and artist fields.
+pub fn query_variants(title: &str, artist: &str, album: &str) -> Vec<QueryTerms> {
+    let mut variants = vec![QueryTerms {
+        title: title.to_string(),
+        artist: artist.to_string(),
+        album: album.to_string(),
+    }];
+    let mut push = |title: String, artist: String| {
+        let terms = QueryTerms {
+            title,
+            artist,
+            album: album.to_string(),
+        };
+        if !variants.contains(&terms) {
+            variants.push(terms);
+        }
+    };
+    if let Some((base, guests)) = split_guest_in_title(title) {
+        push(base.clone(), format!("{artist}, {guests}"));
+        push(base.clone(), format!("{artist} & {guests}"));
+        push(base.clone(), format!("{artist} feat. {guests}"));
+        push(base, artist.to_string());
+    }
+    if let Some((primary, guests)) = split_artists(artist) {
+        push(format!("{title} (feat. {guests})"), primary.clone());
+        push(format!("{title} (with {guests})"), primary.clone());
+        push(title.to_string(), primary);
+    }
+    variants
+}
+
+fn letters(value: &str) -> Vec<char> {
+    value
+        .chars()
+        .flat_map(char::to_lowercase)
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+fn bigrams(chars: &[char]) -> Vec<(char, char)> {
+    chars.windows(2).map(|pair| (pair[0], pair[1])).collect()
+}
+
+/// Dice coefficient over character bigrams, ignoring case, spacing and punctuation.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (letters(a), letters(b));
+    if !a.is_empty() && a == b {
+        return 1.0;
+    }
+    let a = bigrams(&a);
+    let mut b = bigrams(&b);
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let total = a.len() + b.len();
+    let mut shared = 0;
+    for pair in a {
+        if let Some(index) = b.iter().position(|other| *other == pair) {
+            b.swap_remove(index);
+            shared += 1;
+        }
+    }
+    2.0 * shared as f64 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(a: &str, b: &str) -> Option<(String, String)> {
+        Some((a.to_string(), b.to_string()))
+    }
+
+    fn terms(title: &str, artist: &str) -> QueryTerms {
+        QueryTerms {
+            title: title.to_string(),
+            artist: artist.to_string(),
+            album: "Album".to_string(),
+        }
+    }
+
+    #[test]
+    fn guests_in_title() {
+        let cases = [
+            ("Stay (feat. Justin Bieber)", pair("Stay", "Justin Bieber")),
+            (
+                "Señorita (Feat. Camila Cabello)",
+                pair("Señorita", "Camila Cabello"),
+            ),
+            (
+                "Under Pressure [ft. David Bowie]",
+                pair("Under Pressure", "David Bowie"),
+            ),
+            (
+                "Empire State of Mind (featuring Alicia Keys)",
+                pair("Empire State of Mind", "Alicia Keys"),
+            ),
+            (
+                "Shallow (with Bradley Cooper)",
+                pair("Shallow", "Bradley Cooper"),
+            ),
+            ("Lose Control (feat. Missy", pair("Lose Control", "Missy")),
+            ("Umbrella feat. Jay-Z", pair("Umbrella", "Jay-Z")),
+            ("Crazy in Love ft. JAY-Z", pair("Crazy in Love", "JAY-Z")),
+            ("Bohemian Rhapsody", None),
+            ("Live with Me", None),
+            ("Without Me", None),
+            ("Song (feat. )", None),
+        ];
+        for (title, expected) in cases {
+            assert_eq!(split_guest_in_title(title), expected, "{title}");
+        }
+    }
+
+    #[test]
+    fn artist_lists() {
+        let cases = [
+            (
+                "Calvin Harris feat. Rihanna",
+                pair("Calvin Harris", "Rihanna"),
+            ),
+            (
+                "Daft Punk ft. Pharrell Williams",
+                pair("Daft Punk", "Pharrell Williams"),
+            ),
+            ("Eminem Featuring Rihanna", pair("Eminem", "Rihanna")),
+            ("Simon & Garfunkel", pair("Simon", "Garfunkel")),
+            (
+                "Crosby, Stills, Nash & Young",
+                pair("Crosby", "Stills, Nash & Young"),
+            ),
+            (
+                "Lady Gaga with Tony Bennett",
+                pair("Lady Gaga", "Tony Bennett"),
+            ),
+            ("Radiohead", None),
+            ("AC/DC", None),
+            ("& Friends", None),
+        ];
+        for (artist, expected) in cases {
+            assert_eq!(split_artists(artist), expected, "{artist}");
+        }
+    }
+
+    #[test]
+    fn plain_track_has_one_variant() {
+        assert_eq!(
+            query_variants("Paranoid Android", "Radiohead", "Album"),
+            [terms("Paranoid Android", "Radiohead")]
+        );
+    }
+
+    #[test]
+    fn guest_moves_from_title_to_artist() {
+        assert_eq!(
+            query_variants("Stay (feat. Justin Bieber)", "The Kid LAROI", "Album"),
+            [
+                terms("Stay (feat. Justin Bieber)", "The Kid LAROI"),
+                terms("Stay", "The Kid LAROI, Justin Bieber"),
+                terms("Stay", "The Kid LAROI & Justin Bieber"),
+                terms("Stay", "The Kid LAROI feat. Justin Bieber"),
+                terms("Stay", "The Kid LAROI"),
+            ]
+        );
+    }
+
+    #[test]
+    fn guest_moves_from_artist_to_title() {
+        assert_eq!(
+            query_variants(
+                "This Is What You Came For",
+                "Calvin Harris feat. Rihanna",
+                "Album"
+            ),
+            [
+                terms("This Is What You Came For", "Calvin Harris feat. Rihanna"),
+                terms("This Is What You Came For (feat. Rihanna)", "Calvin Harris"),
+                terms("This Is What You Came For (with Rihanna)", "Calvin Harris"),
+                terms("This Is What You Came For", "Calvin Harris"),
+            ]
+        );
+    }
+
+    #[test]
+    fn variants_are_unique() {
+        let variants = query_variants(
+            "Shallow (with Bradley Cooper)",
+            "Lady Gaga & Bradley Cooper",
+            "Album",
+        );
+        assert_eq!(
+            variants[0],
+            terms(
+                "Shallow (with Bradley Cooper)",
+                "Lady Gaga & Bradley Cooper"
+            )
+        );
+        for (index, variant) in variants.iter().enumerate() {
+            assert!(!variants[index + 1..].contains(variant), "{variant:?}");
+        }
+    }
+
+    #[test]
+    fn similarity_ignores_case_and_punctuation() {
+        assert_eq!(similarity("Don't Stop Me Now", "dont stop me now"), 1.0);
+        assert_eq!(similarity("AC/DC", "ACDC"), 1.0);
+        assert_eq!(similarity("Beyoncé", "BEYONCÉ"), 1.0);
+        assert_eq!(similarity("a", "A"), 1.0);
+    }
+
+    #[test]
+    fn similarity_ranks_near_matches() {
+        let exact = similarity("Bohemian Rhapsody", "Bohemian Rhapsody");
+        let remaster = similarity("Bohemian Rhapsody", "Bohemian Rhapsody - Remastered 2011");
+        let other = similarity("Bohemian Rhapsody", "Killer Queen");
+        assert_eq!(exact, 1.0);
+        assert!(remaster > 0.5 && remaster < 1.0, "{remaster}");
+        assert!(other < 0.2, "{other}");
+        assert_eq!(similarity("Hello", "World"), similarity("World", "Hello"));
+    }
+
+    #[test]
+    fn similarity_of_empty_values() {
+        assert_eq!(similarity("", ""), 0.0);
+        assert_eq!(similarity("Song", ""), 0.0);
+        assert_eq!(similarity("!!!", "???"), 0.0);
+        assert_eq!(similarity("a", "b"), 0.0);
+    }
+}